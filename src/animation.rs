@@ -0,0 +1,502 @@
+use crate::export;
+use crate::grid::Grid;
+use crate::recording::{Asciicast, Gif, Recorder};
+use crossterm::cursor::{
+    Hide, MoveDown, MoveRight, MoveToPreviousLine, RestorePosition, SavePosition, Show,
+};
+use crossterm::style::{Attribute, Color, ContentStyle, Print, PrintStyledContent, StyledContent};
+use crossterm::terminal;
+use crossterm::QueueableCommand;
+use signal_hook::cleanup;
+use std::env;
+use std::io::{stdout, IsTerminal, Stdout, Write};
+use std::panic;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Once;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A character on screen and how to style it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Cell {
+    pub c: char,
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+}
+
+impl Cell {
+    pub fn new(c: char) -> Self {
+        Cell {
+            c,
+            fg: None,
+            bg: None,
+            bold: false,
+        }
+    }
+
+    pub fn fg(self, color: Color) -> Self {
+        Cell {
+            fg: Some(color),
+            ..self
+        }
+    }
+
+    pub fn on(self, color: Color) -> Self {
+        Cell {
+            bg: Some(color),
+            ..self
+        }
+    }
+
+    pub fn bold(self) -> Self {
+        Cell { bold: true, ..self }
+    }
+
+    pub(crate) fn styled(self) -> StyledContent<char> {
+        let mut style = ContentStyle::new();
+
+        if let Some(color) = self.fg {
+            style = style.foreground(color);
+        }
+        if let Some(color) = self.bg {
+            style = style.background(color);
+        }
+        if self.bold {
+            style = style.attribute(Attribute::Bold);
+        }
+
+        StyledContent::new(style, self.c)
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell::new(' ')
+    }
+}
+
+pub type Frame = Grid<Cell>;
+
+/// Builds a frame from lines of unstyled text, padding short lines.
+pub fn text_frame(text: &str) -> Frame {
+    let width = text
+        .lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let rows = text
+        .lines()
+        .map(|line| {
+            let mut row = line.chars().map(Cell::new).collect::<Vec<_>>();
+            row.resize(width, Cell::default());
+            row
+        })
+        .collect();
+
+    Grid::from_rows(rows).unwrap()
+}
+
+/// Whether to animate at all: not when stdout isn't a terminal, when
+/// `--no-animation` is passed, or when `NO_ANIMATION` is set.
+pub fn enabled() -> bool {
+    stdout().is_terminal()
+        && env::var_os("NO_ANIMATION").is_none()
+        && !env::args().any(|arg| arg == "--no-animation")
+}
+
+/// Whether `<prefix>_CAST` or `<prefix>_GIF` ask for a recording.
+pub fn recording(prefix: &str) -> bool {
+    ["CAST", "GIF"]
+        .iter()
+        .any(|format| env::var_os(format!("{}_{}", prefix, format)).is_some())
+}
+
+/// A run of cells in a row that need redrawing, starting at `(x, y)`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Run {
+    pub x: usize,
+    pub y: usize,
+    pub cells: Vec<Cell>,
+}
+
+// Cells outside a frame count as blank, so a shrinking frame clears behind it
+fn cell(frame: &Frame, x: usize, y: usize) -> Cell {
+    frame.get((x as i64, y as i64)).cloned().unwrap_or_default()
+}
+
+/// Everything that differs between what is on screen and the next frame.
+pub(crate) fn changes(front: Option<&Frame>, back: &Frame) -> Vec<Run> {
+    let (width, height) = match front {
+        Some(front) => (
+            front.width().max(back.width()),
+            front.height().max(back.height()),
+        ),
+        None => (back.width(), back.height()),
+    };
+
+    let mut runs = Vec::new();
+
+    for y in 0..height {
+        let mut run: Option<Run> = None;
+
+        for x in 0..width {
+            let new = cell(back, x, y);
+
+            if front.map(|front| cell(front, x, y)) == Some(new) {
+                runs.extend(run.take());
+            } else {
+                run.get_or_insert(Run {
+                    x,
+                    y,
+                    cells: Vec::new(),
+                })
+                .cells
+                .push(new);
+            }
+        }
+
+        runs.extend(run);
+    }
+
+    runs
+}
+
+// What the panic hook and the Ctrl-C handler need to put the terminal back
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static HEIGHT: AtomicUsize = AtomicUsize::new(0);
+static HOOKS: Once = Once::new();
+
+/// Moves below the animation and shows the cursor again, once.
+fn restore(out: &mut impl Write) {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return;
+    }
+
+    out.queue(RestorePosition).unwrap();
+
+    let height = HEIGHT.load(Ordering::SeqCst);
+    if height > 0 {
+        out.queue(MoveDown(height as u16)).unwrap();
+    }
+
+    out.queue(Show).unwrap();
+    out.flush().unwrap();
+}
+
+/// Does what `restore` does from inside a signal handler, which can't lock
+/// stdout or allocate, then lets Ctrl-C stop the process as it normally would.
+fn interrupted() {
+    if ACTIVE.swap(false, Ordering::SeqCst) {
+        let mut buffer = [0u8; 32];
+        let mut out = &mut buffer[..];
+
+        let _ = write!(out, "\x1B8");
+        let height = HEIGHT.load(Ordering::SeqCst);
+        if height > 0 {
+            let _ = write!(out, "\x1B[{}B", height as u16);
+        }
+        let _ = write!(out, "\x1B[?25h");
+
+        let unused = out.len();
+        let len = buffer.len() - unused;
+        unsafe {
+            libc::write(1, buffer.as_ptr() as *const libc::c_void, len);
+        }
+    }
+
+    // Blocked until the handler returns, when the default action takes it
+    cleanup::cleanup_raw(signal_hook::SIGINT);
+    unsafe {
+        libc::raise(signal_hook::SIGINT);
+    }
+}
+
+fn install_hooks() {
+    HOOKS.call_once(|| {
+        let previous = panic::take_hook();
+
+        panic::set_hook(Box::new(move |info| {
+            restore(&mut stdout());
+            previous(info);
+        }));
+
+        // The handler only touches atomics and async-signal-safe calls
+        unsafe { signal_hook::register(signal_hook::SIGINT, interrupted) }.ok();
+    });
+}
+
+/// Draws frames in place on the terminal, rewriting only the cells that
+/// changed since the last one and holding each frame for the frame time.
+/// The viewport grows to fit the frames, up to the size of the terminal.
+///
+/// When animation is disabled frames are only kept, and the last one is
+/// printed as plain text by `finish`.
+///
+/// Every frame also goes to the recorders, timed by the frame rate rather
+/// than the clock, whether or not it is drawn.
+pub struct Animation {
+    stdout: Stdout,
+    enabled: bool,
+    print: bool,
+    front: Option<Frame>,
+    last: Option<Frame>,
+    height: usize,
+    frame_time: Duration,
+    frames: u32,
+    shown: Option<Instant>,
+    recorders: Vec<Box<dyn Recorder>>,
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Animation {
+            stdout: stdout(),
+            enabled: enabled(),
+            print: true,
+            front: None,
+            last: None,
+            height: 0,
+            frame_time: Duration::from_millis(33),
+            frames: 0,
+            shown: None,
+            recorders: Vec::new(),
+        }
+    }
+
+    /// Never draws or prints anything, for feeding recorders.
+    pub fn offline() -> Self {
+        let mut animation = Animation::new();
+        animation.enabled = false;
+        animation.print = false;
+        animation
+    }
+
+    pub fn fps(mut self, fps: u32) -> Self {
+        self.frame_time = Duration::from_secs(1) / fps.max(1);
+        self
+    }
+
+    pub fn record(mut self, recorder: impl Recorder + 'static) -> Self {
+        self.recorders.push(Box::new(recorder));
+        self
+    }
+
+    /// Records to the paths in `<prefix>_CAST` and `<prefix>_GIF` when they
+    /// are set, with GIF cells `EXPORT_SCALE` pixels across.
+    pub fn record_from_env(mut self, prefix: &str) -> Self {
+        if let Ok(path) = env::var(format!("{}_CAST", prefix)) {
+            self = self.record(Asciicast::new(path));
+        }
+
+        if let Ok(path) = env::var(format!("{}_GIF", prefix)) {
+            let gif = match export::env_scale() {
+                Some(scale) => Gif::new(path).scale(scale),
+                None => Gif::new(path),
+            };
+            self = self.record(gif);
+        }
+
+        self
+    }
+
+    pub fn is_recording(&self) -> bool {
+        !self.recorders.is_empty()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn start(&mut self) {
+        install_hooks();
+
+        self.stdout
+            .queue(Hide)
+            .unwrap()
+            .queue(SavePosition)
+            .unwrap();
+        ACTIVE.store(true, Ordering::SeqCst);
+    }
+
+    /// Makes room for `height` rows below the start of the animation,
+    /// scrolling the terminal if needed.
+    fn reserve(&mut self, height: usize) {
+        if height <= self.height {
+            return;
+        }
+
+        self.stdout.queue(RestorePosition).unwrap();
+        if self.height > 0 {
+            self.stdout.queue(MoveDown(self.height as u16)).unwrap();
+        }
+
+        for _ in self.height..height {
+            self.stdout.queue(Print('\n')).unwrap();
+        }
+
+        self.stdout
+            .queue(MoveToPreviousLine(height as u16))
+            .unwrap()
+            .queue(SavePosition)
+            .unwrap();
+
+        self.height = height;
+        HEIGHT.store(height, Ordering::SeqCst);
+    }
+
+    pub fn draw(&mut self, frame: &Frame) {
+        let at = self.frame_time * self.frames;
+        self.frames += 1;
+
+        for recorder in &mut self.recorders {
+            recorder.frame(frame, at);
+        }
+
+        if !self.enabled {
+            if self.print {
+                self.last = Some(frame.clone());
+            }
+            return;
+        }
+
+        if !ACTIVE.load(Ordering::SeqCst) {
+            self.start();
+        }
+
+        // Keep the bottom line free for the cursor. Some terminals don't
+        // know their size, so don't limit the viewport then.
+        let (columns, rows) = terminal::size()
+            .ok()
+            .filter(|&(columns, rows)| columns > 0 && rows > 0)
+            .unwrap_or((u16::MAX, u16::MAX));
+        let (columns, rows) = (columns as usize, (rows as usize).saturating_sub(1));
+
+        self.reserve(frame.height().min(rows));
+
+        for run in changes(self.front.as_ref(), frame) {
+            if run.y >= self.height || run.x >= columns {
+                continue;
+            }
+
+            self.stdout.queue(RestorePosition).unwrap();
+            if run.y > 0 {
+                self.stdout.queue(MoveDown(run.y as u16)).unwrap();
+            }
+            if run.x > 0 {
+                self.stdout.queue(MoveRight(run.x as u16)).unwrap();
+            }
+
+            for cell in run.cells.into_iter().take(columns - run.x) {
+                self.stdout
+                    .queue(PrintStyledContent(cell.styled()))
+                    .unwrap();
+            }
+        }
+
+        self.stdout.flush().unwrap();
+        self.front = Some(frame.clone());
+
+        if let Some(shown) = self.shown {
+            if let Some(wait) = self.frame_time.checked_sub(shown.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+        self.shown = Some(Instant::now());
+    }
+
+    /// Leaves the cursor below the animation, or prints the last frame when
+    /// not animating.
+    pub fn finish(&mut self) {
+        if let Some(frame) = self.last.take() {
+            for row in frame.rows() {
+                let line = row.iter().map(|cell| cell.c).collect::<String>();
+                writeln!(self.stdout, "{}", line.trim_end()).unwrap();
+            }
+        }
+
+        let end = self.frame_time * self.frames;
+        for mut recorder in self.recorders.drain(..) {
+            if let Err(error) = recorder.finish(end) {
+                eprintln!("Error saving recording: {}", error);
+            }
+        }
+
+        restore(&mut self.stdout);
+    }
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Animation::new()
+    }
+}
+
+impl Drop for Animation {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_changes() {
+        let first = text_frame("ab\ncd");
+        let runs = changes(None, &first);
+        assert_eq!(2, runs.len());
+        assert_eq!(
+            Run {
+                x: 0,
+                y: 1,
+                cells: vec![Cell::new('c'), Cell::new('d')]
+            },
+            runs[1]
+        );
+
+        // Only the changed cell is redrawn, and a new column is drawn in full
+        let second = text_frame("ab!\nxd!");
+        assert_eq!(
+            vec![
+                Run {
+                    x: 2,
+                    y: 0,
+                    cells: vec![Cell::new('!')]
+                },
+                Run {
+                    x: 0,
+                    y: 1,
+                    cells: vec![Cell::new('x')]
+                },
+                Run {
+                    x: 2,
+                    y: 1,
+                    cells: vec![Cell::new('!')]
+                },
+            ],
+            changes(Some(&first), &second)
+        );
+
+        // Restyling counts as a change, and cells that go away are blanked
+        let mut third = text_frame("ab");
+        *third.get_mut((1, 0)).unwrap() = Cell::new('b').fg(Color::Red);
+        assert_eq!(
+            vec![
+                Run {
+                    x: 1,
+                    y: 0,
+                    cells: vec![Cell::new('b').fg(Color::Red), Cell::default()]
+                },
+                Run {
+                    x: 0,
+                    y: 1,
+                    cells: vec![Cell::default(); 3]
+                },
+            ],
+            changes(Some(&second), &third)
+        );
+    }
+}
@@ -1,202 +1,514 @@
+use crate::animation::{Animation, Cell, Frame};
+use crate::export::{self, Image, BLACK, RED, WHITE};
+use crate::grid::Grid;
 use aoc_runner_derive::{aoc, aoc_generator};
-use crossterm::cursor::{Hide, MoveUp, Show};
-use crossterm::style::{Colorize, Print, PrintStyledContent, Styler};
-use crossterm::{ExecutableCommand, QueueableCommand};
-use nom::branch::alt;
-use nom::character::complete::{char, line_ending};
-use nom::multi::{many1, separated_list};
-use nom::IResult;
+use crossterm::style::Color;
 use num::integer::gcd;
-use std::collections::HashSet;
-use std::f32::consts::PI;
-use std::io::{stdout, Write};
-use std::thread::sleep;
-use std::time::Duration;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::env;
+use std::fmt::{Debug, Display, Error, Formatter};
+
+fn asteroid(c: char) -> Option<bool> {
+    match c {
+        '#' => Some(true),
+        '.' => Some(false),
+        _ => None,
+    }
+}
 
-fn asteroid(input: &str) -> IResult<&str, bool> {
-    let (input, ast) = alt((char('.'), char('#')))(input)?;
+#[aoc_generator(day10)]
+fn parse_map(input: &str) -> Grid<bool> {
+    Grid::parse(input, asteroid).unwrap()
+}
 
-    Ok((input, ast == '#'))
+/// The way from one asteroid to another, as the offset divided by its gcd so
+/// that everything along the same line of sight shares it. Directions order
+/// clockwise from straight up, with `y` growing down the map.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+struct Direction {
+    dx: i64,
+    dy: i64,
 }
 
-#[aoc_generator(day10)]
-fn parse_map(input: &str) -> Vec<Vec<bool>> {
-    separated_list(line_ending, many1(asteroid))(input)
-        .unwrap()
-        .1
-}
-
-fn asteroids_detected(map: &Vec<Vec<bool>>, (x, y): (usize, usize)) -> u32 {
-    let mut count = 0;
-
-    for i in 0..map.len() {
-        for j in 0..map[0].len() {
-            let dy = i as i32 - y as i32;
-            let dx = j as i32 - x as i32;
-
-            if gcd(dx, dy) == 1 {
-                let mut y = y as i32;
-                let mut x = x as i32;
-
-                loop {
-                    y += dy;
-                    x += dx;
-
-                    let ast = map.get(y as usize).and_then(|line| line.get(x as usize));
-
-                    match ast {
-                        // Asteroid found, increment and stop search
-                        Some(true) => {
-                            count += 1;
-                            break;
-                        }
-                        // No asteroid found, continue searching in line of sight
-                        Some(false) => {
-                            continue;
-                        }
-                        // End of map
-                        None => {
-                            break;
-                        }
-                    }
-                }
-            }
+impl Direction {
+    const UP: Direction = Direction { dx: 0, dy: -1 };
+
+    fn new(dx: i64, dy: i64) -> Option<Self> {
+        if dx == 0 && dy == 0 {
+            return None;
         }
+
+        let d = gcd(dx, dy);
+        Some(Direction {
+            dx: dx / d,
+            dy: dy / d,
+        })
+    }
+
+    fn between((x1, y1): (i64, i64), (x2, y2): (i64, i64)) -> Option<Self> {
+        Direction::new(x2 - x1, y2 - y1)
     }
 
-    count
+    // 0 from straight up round to just before straight down, 1 for the rest
+    fn half(self) -> u8 {
+        if self.dx > 0 || (self.dx == 0 && self.dy < 0) {
+            0
+        } else {
+            1
+        }
+    }
 }
 
-fn find_placement(map: &Vec<Vec<bool>>) -> ((usize, usize), u32) {
-    let mut max_count = 0;
-    let mut pos = (0, 0);
+impl Ord for Direction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Within a half the cross product is positive when `other` is further
+        // clockwise, and zero only for the same reduced direction
+        let cross = self.dx * other.dy - self.dy * other.dx;
 
-    for y in 0..map.len() {
-        for x in 0..map[0].len() {
-            if map[y][x] {
-                let count = asteroids_detected(map, (x, y));
+        self.half().cmp(&other.half()).then_with(|| 0.cmp(&cross))
+    }
+}
 
-                if count > max_count {
-                    max_count = count;
-                    pos = (x, y);
-                }
-            }
-        }
+impl PartialOrd for Direction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
+}
+
+fn asteroids(map: &Grid<bool>) -> Vec<(i64, i64)> {
+    map.iter()
+        .filter(|&(_, &ast)| ast)
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+/// Asteroids hide each other when they share a direction, so the ones in
+/// sight are the distinct directions to all the others.
+fn asteroids_detected(asteroids: &[(i64, i64)], station: (i64, i64)) -> usize {
+    asteroids
+        .iter()
+        .filter_map(|&pos| Direction::between(station, pos))
+        .collect::<HashSet<_>>()
+        .len()
+}
+
+fn find_placement(map: &Grid<bool>) -> Option<((i64, i64), usize)> {
+    let asteroids = asteroids(map);
 
-    (pos, max_count)
+    asteroids
+        .iter()
+        .map(|&pos| (pos, asteroids_detected(&asteroids, pos)))
+        // The first of any ties, as `max_by_key` keeps the last
+        .rev()
+        .max_by_key(|&(_, count)| count)
 }
 
+/// Set `DAY10_EXPORT` to an image path to save the field there, with the
+/// monitoring station in red.
 #[aoc(day10, part1)]
-fn max_asteroids_detected(map: &Vec<Vec<bool>>) -> u32 {
-    find_placement(map).1
-}
-
-fn angle((x, y): (i32, i32)) -> f32 {
-    let a = (x as f32).atan2(-y as f32);
-
-    if a >= 0.0 {
-        a
-    } else {
-        a + 2.0 * PI
-    }
-}
-
-fn print_map(
-    map: &Vec<Vec<bool>>,
-    term: &mut impl Write,
-    x: i32,
-    y: i32,
-    laser: &(usize, usize),
-    found: &HashSet<(i32, i32)>,
-) {
-    for j in 0..map.len() {
-        for i in 0..map[0].len() {
-            if laser.0 == i && laser.1 == j {
-                term.queue(PrintStyledContent("X".cyan())).unwrap();
-            } else if i == x as usize && j == y as usize {
-                term.queue(PrintStyledContent("#".red())).unwrap();
-            } else if found.contains(&(i as i32, j as i32)) {
-                term.queue(PrintStyledContent("#".green())).unwrap();
-            } else if map[j][i] {
-                term.queue(PrintStyledContent("#".reset())).unwrap();
-            } else {
-                term.queue(PrintStyledContent(".".reset())).unwrap();
-            }
+fn max_asteroids_detected(map: &Grid<bool>) -> usize {
+    let placement = find_placement(map);
+
+    let mut colors = map.map(|&ast| if ast { WHITE } else { BLACK });
+    if let Some((station, _)) = placement {
+        *colors.get_mut(station).unwrap() = RED;
+    }
+
+    let image = Image::from_grid(&colors, |&color| color);
+    export::save_to_env("DAY10_EXPORT", image).unwrap();
+
+    placement.map_or(0, |(_, count)| count)
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Rotation {
+    Clockwise,
+    Counterclockwise,
+}
+
+#[derive(Eq, PartialEq)]
+enum LaserError {
+    TooFew {
+        wanted: usize,
+        found: usize,
+    },
+    NoAsteroids,
+    /// The environment variable `var` holds something other than `expected`.
+    Setting {
+        var: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+}
+
+impl Display for LaserError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            LaserError::TooFew { wanted, found } => write!(
+                f,
+                "Wanted asteroid {} but the laser only destroys {}",
+                wanted, found
+            ),
+            LaserError::NoAsteroids => write!(f, "No asteroids to put the station on"),
+            LaserError::Setting {
+                var,
+                value,
+                expected,
+            } => write!(f, "{} should be {}, got {:?}", var, expected, value),
         }
-        term.queue(Print("\n")).unwrap();
     }
-    term.flush().unwrap();
 }
 
-#[aoc(day10, part2)]
-fn find_200th(map: &Vec<Vec<bool>>) -> i32 {
-    let laser = find_placement(map).0;
+impl Debug for LaserError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        Display::fmt(self, f)
+    }
+}
 
-    let mut angles = Vec::new();
+/// Asteroids in the order a laser at the station destroys them. Each time it
+/// passes a direction it hits the nearest asteroid left there, and it keeps
+/// sweeping round until none are left.
+struct Vaporization {
+    rotation: Rotation,
+    start: Direction,
+    // The asteroids in each direction, farthest first so the nearest pops off
+    targets: VecDeque<(Direction, Vec<(i64, i64)>)>,
+}
 
-    for j in 0..map.len() {
-        for i in 0..map[0].len() {
-            let y = j as i32 - laser.1 as i32;
-            let x = i as i32 - laser.0 as i32;
+impl Vaporization {
+    /// Turns the laser the other way, starting over from `start`.
+    fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self.arrange();
+        self
+    }
 
-            if !(laser.0 == x as usize && laser.1 == y as usize) && gcd(x, y) == 1 {
-                angles.push((x, y));
-            }
+    /// Points the laser in another direction, sweeping from there. It hits
+    /// anything in that exact direction first.
+    fn start(mut self, start: Direction) -> Self {
+        self.start = start;
+        self.arrange();
+        self
+    }
+
+    fn arrange(&mut self) {
+        let (rotation, start) = (self.rotation, self.start);
+        let ahead = |dir: Direction| match rotation {
+            Rotation::Clockwise => dir >= start,
+            Rotation::Counterclockwise => dir <= start,
+        };
+
+        self.targets.make_contiguous().sort_by(|(a, _), (b, _)| {
+            ahead(*b).cmp(&ahead(*a)).then_with(|| match rotation {
+                Rotation::Clockwise => a.cmp(b),
+                Rotation::Counterclockwise => b.cmp(a),
+            })
+        });
+    }
+}
+
+impl Iterator for Vaporization {
+    type Item = (i64, i64);
+
+    fn next(&mut self) -> Option<(i64, i64)> {
+        let (dir, mut asteroids) = self.targets.pop_front()?;
+        let target = asteroids.pop();
+
+        if !asteroids.is_empty() {
+            self.targets.push_back((dir, asteroids));
         }
+
+        target
     }
+}
 
-    angles.sort_unstable_by(|&a, &b| angle(a).partial_cmp(&angle(b)).unwrap());
+/// Every asteroid but the station, in the order a laser there destroys them
+/// when it starts pointing up and turns clockwise.
+fn vaporization_order(map: &Grid<bool>, station: (i64, i64)) -> Vaporization {
+    let mut targets = HashMap::new();
 
-    let count = angles.len();
-    let mut found = HashSet::new();
-    let mut term = stdout();
-
-    term.queue(Hide).unwrap();
-    print_map(
-        map,
-        &mut term,
-        laser.0 as i32,
-        laser.1 as i32,
-        &laser,
-        &found,
-    );
-
-    for i in 0.. {
-        let point = &angles[i % count];
-
-        let mut y = laser.1 as i32;
-        let mut x = laser.0 as i32;
-
-        loop {
-            y += point.1;
-            x += point.0;
-
-            let ast = map.get(y as usize).and_then(|line| line.get(x as usize));
-
-            match ast {
-                Some(true) if !found.contains(&(x, y)) => {
-                    term.queue(MoveUp(map.len() as u16)).unwrap();
-                    print_map(map, &mut term, x, y, &laser, &found);
-                    sleep(Duration::from_millis(100));
-
-                    if found.len() == 199 {
-                        term.execute(Show).unwrap();
-                        return x * 100 + y;
-                    } else {
-                        found.insert((x, y));
-                        break;
-                    }
-                }
-                Some(_) => {
-                    continue;
-                }
-                None => {
-                    break;
-                }
-            }
+    for pos in asteroids(map) {
+        if let Some(dir) = Direction::between(station, pos) {
+            targets.entry(dir).or_insert_with(Vec::new).push(pos);
         }
     }
 
-    unreachable!()
+    let targets = targets
+        .into_iter()
+        .map(|(dir, mut asteroids)| {
+            // Along one direction the distance grows with either offset
+            asteroids.sort_unstable_by_key(|&(x, y)| {
+                Reverse((x - station.0).abs() + (y - station.1).abs())
+            });
+            (dir, asteroids)
+        })
+        .collect();
+
+    let mut vaporization = Vaporization {
+        rotation: Rotation::Clockwise,
+        start: Direction::UP,
+        targets,
+    };
+    vaporization.arrange();
+    vaporization
+}
+
+/// The `n`th asteroid destroyed, counting from 1.
+fn nth_vaporized(
+    order: impl Iterator<Item = (i64, i64)>,
+    n: usize,
+) -> Result<(i64, i64), LaserError> {
+    let mut found = 0;
+
+    for pos in order {
+        found += 1;
+
+        if found == n {
+            return Ok(pos);
+        }
+    }
+
+    Err(LaserError::TooFew { wanted: n, found })
+}
+
+fn parse_pair(var: &'static str, value: &str) -> Result<(i64, i64), LaserError> {
+    value
+        .split_once(',')
+        .and_then(|(a, b)| Some((a.trim().parse().ok()?, b.trim().parse().ok()?)))
+        .ok_or_else(|| LaserError::Setting {
+            var,
+            value: value.to_string(),
+            expected: "two integers separated by a comma",
+        })
+}
+
+fn env_pair(var: &'static str) -> Result<Option<(i64, i64)>, LaserError> {
+    env::var(var)
+        .ok()
+        .map(|value| parse_pair(var, &value))
+        .transpose()
+}
+
+fn parse_rotation(value: &str) -> Result<Rotation, LaserError> {
+    match value {
+        "clockwise" => Ok(Rotation::Clockwise),
+        "counterclockwise" => Ok(Rotation::Counterclockwise),
+        _ => Err(LaserError::Setting {
+            var: "DAY10_ROTATION",
+            value: value.to_string(),
+            expected: "clockwise or counterclockwise",
+        }),
+    }
+}
+
+fn parse_start((dx, dy): (i64, i64)) -> Result<Direction, LaserError> {
+    Direction::new(dx, dy).ok_or_else(|| LaserError::Setting {
+        var: "DAY10_START",
+        value: format!("{},{}", dx, dy),
+        expected: "a direction other than 0,0",
+    })
+}
+
+fn map_frame(
+    map: &Grid<bool>,
+    target: (i64, i64),
+    station: (i64, i64),
+    found: &HashSet<(i64, i64)>,
+) -> Frame {
+    let mut frame = Frame::new(map.width(), map.height(), Cell::default());
+
+    for (pos, &asteroid) in map.iter() {
+        let cell = if pos == station {
+            Cell::new('X').fg(Color::Cyan)
+        } else if pos == target {
+            Cell::new('#').fg(Color::Red)
+        } else if found.contains(&pos) {
+            Cell::new('#').fg(Color::Green)
+        } else if asteroid {
+            Cell::new('#')
+        } else {
+            Cell::new('.')
+        };
+
+        *frame.get_mut(pos).unwrap() = cell;
+    }
+
+    frame
+}
+
+/// Set `DAY10_STATION` to `x,y` to put the station somewhere else,
+/// `DAY10_ROTATION` to `counterclockwise` to turn the laser the other way and
+/// `DAY10_START` to `dx,dy` for the direction it starts pointing in. Set
+/// `DAY10_CAST` or `DAY10_GIF` to a path to record the laser there.
+#[aoc(day10, part2)]
+fn find_200th(map: &Grid<bool>) -> i64 {
+    let station = match env_pair("DAY10_STATION").unwrap() {
+        Some(station) => station,
+        None => {
+            let (station, _) = find_placement(map).ok_or(LaserError::NoAsteroids).unwrap();
+            station
+        }
+    };
+
+    let mut order = vaporization_order(map, station);
+
+    if let Ok(value) = env::var("DAY10_ROTATION") {
+        order = order.rotation(parse_rotation(&value).unwrap());
+    }
+
+    if let Some(start) = env_pair("DAY10_START").unwrap() {
+        order = order.start(parse_start(start).unwrap());
+    }
+
+    let mut found = HashSet::new();
+    let mut animation = Animation::new().fps(10).record_from_env("DAY10");
+
+    animation.draw(&map_frame(map, station, station, &found));
+
+    let order = order.inspect(|&pos| {
+        animation.draw(&map_frame(map, pos, station, &found));
+        found.insert(pos);
+    });
+    let (x, y) = nth_vaporized(order, 200).unwrap();
+
+    animation.finish();
+
+    x * 100 + y
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_direction_order() {
+        let clockwise = [
+            (0, -1),
+            (1, -3),
+            (1, -1),
+            (1, 0),
+            (2, 1),
+            (0, 1),
+            (-1, 1),
+            (-1, 0),
+            (-3, -1),
+        ];
+        let mut directions = clockwise
+            .iter()
+            .rev()
+            .map(|&(dx, dy)| Direction::new(dx, dy).unwrap())
+            .collect::<Vec<_>>();
+        directions.sort();
+
+        assert_eq!(
+            clockwise.to_vec(),
+            directions.iter().map(|d| (d.dx, d.dy)).collect::<Vec<_>>()
+        );
+        assert_eq!(Direction::new(2, -6), Direction::between((1, 1), (2, -2)));
+        assert_eq!(None, Direction::new(0, 0));
+    }
+
+    #[test]
+    fn test_detected() {
+        let map = parse_map(".#..#\n.....\n#####\n....#\n...##");
+
+        assert_eq!(Some(((3, 4), 8)), find_placement(&map));
+        assert_eq!(7, asteroids_detected(&asteroids(&map), (1, 0)));
+
+        let empty = parse_map("...\n...");
+        assert_eq!(None, find_placement(&empty));
+        assert_eq!(0, max_asteroids_detected(&empty));
+    }
+
+    const LARGE: &str = ".#..##.###...#######
+##.############..##.
+.#.######.########.#
+.###.#######.####.#.
+#####.##.#.##.###.##
+..#####..#.#########
+####################
+#.####....###.#.#.##
+##.#################
+#####.##.###..####..
+..######..##.#######
+####.##.####...##..#
+.#####..#.######.###
+##...#.##########...
+#.##########.#######
+.####.#.###.###.#.##
+....##.##.###..#####
+.#.#.###########.###
+#.#.#.#####.####.###
+###.##.####.##.#..##";
+
+    #[test]
+    fn test_vaporization_order() {
+        let map = parse_map(LARGE);
+        let station = find_placement(&map).unwrap().0;
+        let order = vaporization_order(&map, station).collect::<Vec<_>>();
+
+        assert_eq!((11, 13), station);
+        assert_eq!(299, order.len());
+        assert_eq!(&[(11, 12), (12, 1), (12, 2)], &order[..3]);
+        assert_eq!((8, 2), order[199]);
+        assert_eq!((11, 1), order[298]);
+
+        assert_eq!(
+            Err(LaserError::TooFew {
+                wanted: 300,
+                found: 299
+            }),
+            nth_vaporized(order.into_iter(), 300)
+        );
+    }
+
+    #[test]
+    fn test_sweep() {
+        let map = parse_map("..#..\n.###.\n#####\n.###.\n..#..");
+        let order = |rotation, start: (i64, i64)| {
+            vaporization_order(&map, (2, 2))
+                .rotation(rotation)
+                .start(Direction::new(start.0, start.1).unwrap())
+                .take(5)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            vec![(2, 1), (1, 1), (1, 2), (1, 3), (2, 3)],
+            order(Rotation::Counterclockwise, (0, -1))
+        );
+        assert_eq!(
+            vec![(3, 2), (3, 3), (2, 3), (1, 3), (1, 2)],
+            order(Rotation::Clockwise, (1, 0))
+        );
+        // Starting between directions picks up at the next one round
+        assert_eq!(
+            vec![(2, 3), (1, 3), (1, 2), (1, 1), (2, 1)],
+            order(Rotation::Clockwise, (1, 4))
+        );
+    }
+
+    #[test]
+    fn test_settings() {
+        assert_eq!(Ok((11, -13)), parse_pair("DAY10_STATION", "11, -13"));
+        assert_eq!(
+            Err(LaserError::Setting {
+                var: "DAY10_STATION",
+                value: "11;13".to_string(),
+                expected: "two integers separated by a comma",
+            }),
+            parse_pair("DAY10_STATION", "11;13")
+        );
+        assert!(parse_pair("DAY10_START", "1,x").is_err());
+
+        assert_eq!(
+            Ok(Rotation::Counterclockwise),
+            parse_rotation("counterclockwise")
+        );
+        assert!(parse_rotation("widdershins").is_err());
+
+        assert_eq!(Direction::new(0, 2), parse_start((0, 2)).ok());
+        assert!(parse_start((0, 0)).is_err());
+    }
 }
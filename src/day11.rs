@@ -1,117 +1,306 @@
 use aoc_runner_derive::aoc;
-use crate::intcode::computer::Computer;
-use std::thread;
-
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right
-}
-
-use Direction::*;
-use std::sync::mpsc::channel;
+use crate::export::{self, Image, Rgb};
+use crate::geometry::{Dir4, Point2};
+use crate::grid::{Grid, SparseGrid};
+use crate::intcode::computer::{Computer, RuntimeError, Status};
+use crate::intcode::parser::int;
+use crate::ocr;
+use nom::character::complete::{char, line_ending, multispace0};
+use nom::combinator::all_consuming;
+use nom::multi::separated_list;
+use nom::sequence::{delimited, tuple};
+use nom::IResult;
 use std::collections::HashMap;
+use std::env;
+use std::fmt::{Debug, Display, Error, Formatter};
+use std::fs;
+use std::path::Path;
+
+#[derive(Eq, PartialEq)]
+enum PaintError {
+    Runtime(RuntimeError),
+    UnknownColor(i64),
+    UnknownTurn(i64),
+    UnexpectedOutput(Vec<i64>),
+    Io(String),
+    Parse(String),
+}
 
-impl Direction {
-    fn turn(self, dir: i64) -> Direction {
+impl Display for PaintError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         match self {
-            Up if dir == 0 => Left,
-            Up if dir == 1 => Right,
-            Down if dir == 0 => Right,
-            Down if dir == 1 => Left,
-            Left if dir == 0 => Down,
-            Left if dir == 1 => Up,
-            Right if dir == 0 => Up,
-            Right if dir == 1 => Down,
-            _ => unreachable!()
+            PaintError::Runtime(error) => write!(f, "{}", error),
+            PaintError::UnknownColor(color) => write!(f, "Unknown panel color {}", color),
+            PaintError::UnknownTurn(turn) => write!(f, "Robot made unknown turn {}", turn),
+            PaintError::UnexpectedOutput(output) => {
+                write!(f, "Expected a color and a turn, got {:?}", output)
+            }
+            PaintError::Io(error) => write!(f, "Error reading panels: {}", error),
+            PaintError::Parse(reason) => write!(f, "Invalid panels: {}", reason),
         }
     }
+}
 
-    fn next(&self, (x, y): (i64, i64)) -> (i64, i64) {
-        match self  {
-            Up => (x, y + 1),
-            Down => (x, y - 1),
-            Left => (x - 1, y),
-            Right => (x + 1, y),
+impl Debug for PaintError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        Display::fmt(self, f)
+    }
+}
+
+impl From<RuntimeError> for PaintError {
+    fn from(error: RuntimeError) -> Self {
+        PaintError::Runtime(error)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Robot {
+    /// Colors are numbered from 0, which unpainted panels start as.
+    colors: i64,
+    /// Quarter turns clockwise for each turn the program can output.
+    turns: HashMap<i64, i64>,
+    /// Panels already painted before the robot starts.
+    panels: SparseGrid<i64>,
+    max_steps: Option<usize>,
+}
+
+impl Default for Robot {
+    fn default() -> Self {
+        Robot {
+            colors: 2,
+            turns: vec![(0, -1), (1, 1)].into_iter().collect(),
+            panels: SparseGrid::new(),
+            max_steps: None,
         }
     }
 }
 
-fn paint(program: &str, start_color: i64) -> HashMap<(i64, i64), i64> {
-    let mut computer = Computer::load(program).unwrap();
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Step {
+    /// Panel painted, before moving on.
+    pos: (i64, i64),
+    color: i64,
+    /// Direction faced after turning.
+    dir: Dir4,
+}
 
-    let (input, receiver) = channel();
-    let (sender, output) = channel();
+#[derive(Debug, Clone)]
+struct Painting {
+    panels: SparseGrid<i64>,
+    history: Vec<Step>,
+}
 
-    computer.set_input(receiver);
-    computer.set_output(sender);
+impl Painting {
+    /// How many times each panel was painted by the robot.
+    fn repaints(&self) -> HashMap<(i64, i64), usize> {
+        let mut counts = HashMap::new();
 
-    thread::spawn(move || computer.run().unwrap());
+        for step in &self.history {
+            *counts.entry(step.pos).or_insert(0) += 1;
+        }
 
-    let mut panels = HashMap::new();
+        counts
+    }
+}
 
-    let mut dir = Direction::Up;
-    let mut pos = (0, 0);
+impl Robot {
+    fn paint(&self, program: &str) -> Result<Painting, PaintError> {
+        let known = |color: &i64| (0..self.colors).contains(color);
 
-    input.send(start_color).unwrap();
+        if let Some((_, &color)) = self.panels.iter().find(|(_, color)| !known(color)) {
+            return Err(PaintError::UnknownColor(color));
+        }
 
-    loop {
-        let color = match output.recv() {
-            Ok(c) => c,
-            _ => break
-        };
+        let mut computer = Computer::load(program)
+            .map_err(|error| PaintError::Parse(format!("{:?}", error)))?;
+
+        let mut panels = self.panels.clone();
+        let mut history = Vec::new();
 
-        panels.insert(pos, color);
+        let mut dir = Dir4::North;
+        let mut pos = (0, 0);
 
-        let turn = output.recv().ok().unwrap();
-        dir = dir.turn(turn);
-        pos = dir.next(pos);
+        while self.max_steps.filter(|&max| history.len() >= max).is_none() {
+            computer.push_input(panels.get(pos).cloned().unwrap_or(0));
 
-        let color = panels
-            .get(&pos)
-            .cloned()
-            .unwrap_or(0);
+            let status = computer.resume()?;
+            let output = computer.take_output();
 
-        if input.send(color).is_err() {
-            break
+            let (color, turn) = match output[..] {
+                [color, turn] => (color, turn),
+                [] if status == Status::Halted => break,
+                _ => return Err(PaintError::UnexpectedOutput(output)),
+            };
+
+            if !known(&color) {
+                return Err(PaintError::UnknownColor(color));
+            }
+
+            let quarters = *self.turns.get(&turn).ok_or(PaintError::UnknownTurn(turn))?;
+
+            panels.insert(pos, color);
+            dir = dir.rotate(quarters);
+            history.push(Step { pos, color, dir });
+            pos = Point2::from(pos).step(dir).into();
+
+            if status == Status::Halted {
+                break;
+            }
         }
+
+        Ok(Painting { panels, history })
     }
+}
+
+fn panel(input: &str) -> IResult<&str, ((i64, i64), i64)> {
+    let (input, (x, _, y, _, color)) = tuple((int, char(','), int, char(','), int))(input)?;
 
-    panels
+    Ok((input, ((x, y), color)))
+}
+
+/// Reads starting panels, one `x,y,color` per line.
+fn load_panels(path: impl AsRef<Path>) -> Result<SparseGrid<i64>, PaintError> {
+    let text = fs::read_to_string(path).map_err(|error| PaintError::Io(error.to_string()))?;
+
+    let (_, panels) = all_consuming(delimited(
+        multispace0,
+        separated_list(line_ending, panel),
+        multispace0,
+    ))(&text)
+    .map_err(|_| PaintError::Parse("expected lines of x,y,color".to_string()))?;
+
+    Ok(panels.into_iter().collect())
 }
 
 #[aoc(day11, part1)]
 fn count(program: &str) -> usize {
-    let panels = paint(program, 0);
-    panels.len()
+    let painting = Robot::default().paint(program).unwrap();
+    painting.repaints().len()
 }
 
+const PALETTE: [char; 8] = [' ', '#', '+', '*', 'o', '@', '%', '='];
+
+const COLORS: [Rgb; 8] = [
+    [0, 0, 0],
+    [255, 255, 255],
+    [230, 60, 60],
+    [60, 180, 75],
+    [255, 225, 25],
+    [0, 130, 200],
+    [245, 130, 48],
+    [145, 30, 180],
+];
+
+/// Set `DAY11_PANELS` to a file of `x,y,color` lines to start from those
+/// panels instead of a single white one, and `DAY11_EXPORT` to an image
+/// path to save the hull there.
 #[aoc(day11, part2)]
-fn show(program: &str) -> &'static str {
-    let panels = paint(program, 1);
+fn show(program: &str) -> String {
+    let panels = match env::var("DAY11_PANELS") {
+        Ok(path) => load_panels(path).unwrap(),
+        Err(_) => vec![((0, 0), 1)].into_iter().collect(),
+    };
+
+    let robot = Robot {
+        colors: PALETTE.len() as i64,
+        panels,
+        ..Robot::default()
+    };
+    let panels = robot.paint(program).unwrap().panels;
+
+    let (_, min_y, _, max_y) = panels.bounds().unwrap();
+
+    let rows = (min_y..=max_y)
+        .map(|y| panels.row(y).map(|color| color.cloned().unwrap_or(0)).collect())
+        .collect();
+    let hull = Grid::from_rows(rows).unwrap();
+
+    print!("{}", hull.render(|&color| PALETTE[color as usize]));
+
+    let image = Image::from_grid(&hull, |&color| COLORS[color as usize]);
+    export::save_to_env("DAY11_EXPORT", image).unwrap();
+
+    let pixels = hull.map(|&color| color != 0);
+    ocr::recognize(&pixels.rows().collect::<Vec<_>>()).unwrap()
+}
 
-    let (mut min_x, mut min_y, mut max_x, mut max_y) =
-        (std::i64::MAX, std::i64::MAX, std::i64::MIN, std::i64::MIN);
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    for pos in panels.keys() {
-        min_x = min_x.min(pos.0);
-        min_y = min_y.min(pos.1);
-        max_x = max_x.max(pos.0);
-        max_y = max_y.max(pos.1);
+    // Paints each color and makes each turn in order, reading a panel first
+    fn scripted(steps: &[(i64, i64)]) -> String {
+        steps
+            .iter()
+            .map(|(color, turn)| format!("3,100,104,{},104,{},", color, turn))
+            .chain(std::iter::once("99".to_string()))
+            .collect()
     }
 
-    for y in (min_y..=max_y).rev() {
-        for x in min_x..=max_x {
-            let color = panels.get(&(x, y)).cloned().unwrap_or(0);
-            if color == 0 {
-                print!(" ");
-            } else {
-                print!("#");
-            }
-        }
-        println!();
+    #[test]
+    fn test_paint() {
+        let program = scripted(&[(1, 0), (0, 0), (1, 0), (1, 0), (0, 1)]);
+        let painting = Robot::default().paint(&program).unwrap();
+
+        // Around a square anticlockwise and back to the start
+        assert_eq!(
+            vec![(0, 0), (-1, 0), (-1, 1), (0, 1), (0, 0)],
+            painting.history.iter().map(|step| step.pos).collect::<Vec<_>>()
+        );
+        assert_eq!(Some(&2), painting.repaints().get(&(0, 0)));
+        assert_eq!(Some(&0), painting.panels.get((0, 0)));
+        assert_eq!(4, painting.repaints().len());
+
+        let robot = Robot {
+            max_steps: Some(4),
+            ..Robot::default()
+        };
+        let painting = robot.paint(&program).unwrap();
+        assert_eq!(4, painting.history.len());
+        assert_eq!(Some(&1), painting.panels.get((0, 0)));
+    }
+
+    #[test]
+    fn test_turns() {
+        let program = scripted(&[(1, 7), (1, 7)]);
+        let robot = Robot {
+            turns: vec![(7, 2)].into_iter().collect(),
+            ..Robot::default()
+        };
+
+        // Turning around each time shuttles between two panels
+        let painting = robot.paint(&program).unwrap();
+        assert_eq!(Dir4::South, painting.history[0].dir);
+        assert_eq!(Dir4::North, painting.history[1].dir);
+        assert_eq!((0, 1), painting.history[1].pos);
+
+        assert_eq!(
+            Err(PaintError::UnknownTurn(7)),
+            Robot::default().paint(&program).map(|_| ())
+        );
+        assert_eq!(
+            Err(PaintError::UnknownColor(2)),
+            Robot::default().paint(&scripted(&[(2, 0)])).map(|_| ())
+        );
     }
 
-    "PAINTING COMPLETE"
+    #[test]
+    fn test_panel_colors() {
+        let program = scripted(&[(1, 0)]);
+        let robot = |color| Robot {
+            colors: 8,
+            panels: vec![((0, 0), 1), ((3, 2), color)].into_iter().collect(),
+            ..Robot::default()
+        };
+
+        assert!(robot(7).paint(&program).is_ok());
+        assert_eq!(
+            Err(PaintError::UnknownColor(8)),
+            robot(8).paint(&program).map(|_| ())
+        );
+        assert_eq!(
+            Err(PaintError::UnknownColor(-1)),
+            robot(-1).paint(&program).map(|_| ())
+        );
+    }
 }
@@ -1,117 +1,546 @@
+use crate::intcode::parser::int;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 use nom::bytes::complete::tag;
-use nom::character::complete::{char, digit1, line_ending};
-use nom::combinator::{map_res, recognize, opt, map};
+use nom::character::complete::{alpha1, char, space0};
+use nom::combinator::{all_consuming, opt};
 use nom::multi::separated_list;
-use nom::sequence::tuple;
+use nom::sequence::{delimited, preceded, tuple};
 use nom::IResult;
 use num::integer::lcm;
+use std::env;
+use std::fmt::{Debug, Display, Error, Formatter};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::thread;
 
-fn int(input: &str) -> IResult<&str, i64> {
-    map_res(recognize(tuple((opt(char('-')), digit1))), str::parse)(input)
+#[derive(Eq, PartialEq)]
+enum NBodyError {
+    Parse(String),
+    NoBodies,
+    NoAxes,
+    /// A body's position or velocity has a different number of axes.
+    Dimensions {
+        body: usize,
+        found: usize,
+        expected: usize,
+    },
 }
 
-fn pos(axis: char) -> impl Fn(&str) -> IResult<&str, i64> {
-    move |input| {
-        map(tuple((char(axis), char('='), int)), |r| r.2)(input)
+impl Display for NBodyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            NBodyError::Parse(line) => write!(f, "Can't read a body from {:?}", line),
+            NBodyError::NoBodies => write!(f, "There are no bodies to simulate"),
+            NBodyError::NoAxes => write!(f, "Bodies need at least one axis to move along"),
+            NBodyError::Dimensions {
+                body,
+                found,
+                expected,
+            } => write!(f, "Body {} has {} axes, expected {}", body, found, expected),
+        }
     }
 }
 
-fn position(input: &str) -> IResult<&str, [i64; 3]> {
-    let (input, (_, x, _, y, _, z, _)) = tuple((
-        char('<'),
-        pos('x'),
-        tag(", "),
-        pos('y'),
-        tag(", "),
-        pos('z'),
-        char('>'),
-    ))(input)?;
-
-    Ok((input, [x, y, z]))
+impl Debug for NBodyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        Display::fmt(self, f)
+    }
 }
 
-#[aoc_generator(day12)]
-fn parse_positions(input: &str) -> Vec<[i64; 3]> {
-    separated_list(line_ending, position)(input).unwrap().1
+/// A rule for how bodies change each other's velocities. Forces act on each
+/// axis on its own, given every body's position along it.
+trait Force {
+    fn accelerate(&self, positions: &[i64], velocities: &mut [i64]);
 }
 
-fn states_from(positions: &Vec<[i64; 3]>) -> [Vec<(i64, i64)>; 3] {
-    let mut res = [
-        Vec::with_capacity(positions.len()),
-        Vec::with_capacity(positions.len()),
-        Vec::with_capacity(positions.len())
-    ];
+/// The moons' gravity: each pair of bodies pulls one step closer together.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+struct Gravity;
+
+impl Force for Gravity {
+    // Everything above a body pulls it up and everything below pulls it down,
+    // which a sorted copy counts without comparing every pair
+    fn accelerate(&self, positions: &[i64], velocities: &mut [i64]) {
+        let mut sorted = positions.to_vec();
+        sorted.sort_unstable();
+
+        for (position, velocity) in positions.iter().zip(velocities) {
+            let below = sorted.partition_point(|p| p < position);
+            let above = sorted.len() - sorted.partition_point(|p| p <= position);
+
+            *velocity += above as i64 - below as i64;
+        }
+    }
+}
 
-    for position in positions {
-        for i in 0..3 {
-            res[i].push((position[i], 0));
+/// A pull between each pair of bodies, as the change in velocity of a body
+/// at the first position from one at the second.
+impl<F: Fn(i64, i64) -> i64> Force for F {
+    fn accelerate(&self, positions: &[i64], velocities: &mut [i64]) {
+        for (i, j) in (0..positions.len()).tuple_combinations() {
+            velocities[i] += self(positions[i], positions[j]);
+            velocities[j] += self(positions[j], positions[i]);
         }
     }
+}
+
+/// Bodies moving through any number of dimensions. Every step the force
+/// changes their velocities, then they move by their velocities.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct NBody<F = Gravity> {
+    axes: Vec<String>,
+    // Indexed by axis, then body, as forces work one axis at a time
+    positions: Vec<Vec<i64>>,
+    velocities: Vec<Vec<i64>>,
+    force: F,
+    time: u64,
+}
+
+type Vector = Vec<(String, i64)>;
 
-    res
+/// A body's position and velocity.
+type Body = (Vec<i64>, Vec<i64>);
+
+fn component(input: &str) -> IResult<&str, (String, i64)> {
+    let (input, (axis, _, _, value)) = tuple((alpha1, char('='), space0, int))(input)?;
+
+    Ok((input, (axis.to_string(), value)))
+}
+
+fn vector(input: &str) -> IResult<&str, Vector> {
+    delimited(char('<'), separated_list(tag(", "), component), char('>'))(input)
+}
+
+/// Either `<x=1, y=2>` or, as the puzzle prints bodies,
+/// `pos=<x=1, y=2>, vel=<x=0, y=-1>`.
+fn body(input: &str) -> IResult<&str, (Vector, Option<Vector>)> {
+    let (input, position) = preceded(opt(tag("pos=")), vector)(input)?;
+    let (input, velocity) = opt(preceded(tag(", vel="), vector))(input)?;
+
+    Ok((input, (position, velocity)))
+}
+
+impl NBody<Gravity> {
+    /// Reads a body per line, with axes named as in the first one. Bodies
+    /// without a velocity start still.
+    fn parse(input: &str) -> Result<Self, NBodyError> {
+        let bodies = input
+            .lines()
+            .map(|line| {
+                all_consuming(body)(line.trim())
+                    .map(|(_, body)| body)
+                    .map_err(|_| NBodyError::Parse(line.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let axes = match bodies.first() {
+            Some((position, _)) => position.iter().map(|(axis, _)| axis.clone()).collect(),
+            None => return Err(NBodyError::NoBodies),
+        };
+
+        let values = |vector: &Vector| vector.iter().map(|&(_, value)| value).collect();
+        let bodies = bodies
+            .iter()
+            .map(|(position, velocity)| {
+                let position: Vec<i64> = values(position);
+                let velocity = match velocity {
+                    Some(velocity) => values(velocity),
+                    None => vec![0; position.len()],
+                };
+
+                (position, velocity)
+            })
+            .collect();
+
+        NBody::new(axes, bodies, Gravity)
+    }
 }
 
-fn step(state: &mut Vec<(i64, i64)>) {
-    for (i, j) in (0..state.len()).tuple_combinations() {
-        if state[i].0 < state[j].0 {
-            state[i].1 += 1;
-            state[j].1 -= 1;
+impl<F: Force> NBody<F> {
+    /// Takes each body's position and velocity, which need a value for every
+    /// one of `axes`.
+    fn new(axes: Vec<String>, bodies: Vec<Body>, force: F) -> Result<Self, NBodyError> {
+        if bodies.is_empty() {
+            return Err(NBodyError::NoBodies);
         }
 
-        if state[i].0 > state[j].0 {
-            state[i].1 -= 1;
-            state[j].1 += 1;
+        if axes.is_empty() {
+            return Err(NBodyError::NoAxes);
         }
+
+        let expected = axes.len();
+
+        for (body, (position, velocity)) in bodies.iter().enumerate() {
+            for &found in &[position.len(), velocity.len()] {
+                if found != expected {
+                    return Err(NBodyError::Dimensions {
+                        body,
+                        found,
+                        expected,
+                    });
+                }
+            }
+        }
+
+        let by_axis = |vector: fn(&Body) -> &Vec<i64>| {
+            (0..expected)
+                .map(|axis| bodies.iter().map(|body| vector(body)[axis]).collect())
+                .collect()
+        };
+
+        Ok(NBody {
+            positions: by_axis(|body| &body.0),
+            velocities: by_axis(|body| &body.1),
+            axes,
+            force,
+            time: 0,
+        })
     }
 
-    for moon in state {
-        moon.0 += moon.1;
+    fn bodies(&self) -> usize {
+        self.positions[0].len()
     }
-}
 
-#[aoc(day12, part1)]
-fn total_energy(positions: &Vec<[i64; 3]>) -> i64 {
-    let mut states = states_from(positions);
+    fn step(&mut self) {
+        for (positions, velocities) in self.positions.iter_mut().zip(&mut self.velocities) {
+            advance(&self.force, positions, velocities);
+        }
+
+        self.time += 1;
+    }
+
+    fn potential(&self, body: usize) -> i64 {
+        self.positions.iter().map(|axis| axis[body].abs()).sum()
+    }
+
+    fn kinetic(&self, body: usize) -> i64 {
+        self.velocities.iter().map(|axis| axis[body].abs()).sum()
+    }
 
-    for i in 0..3 {
-        for _ in 0..1000 {
-            step(&mut states[i]);
+    /// The total over every body of its potential times its kinetic energy.
+    fn energy(&self) -> i64 {
+        (0..self.bodies())
+            .map(|body| self.potential(body) * self.kinetic(body))
+            .sum()
+    }
+
+    /// Runs `steps` more steps, writing a row for every body at each time
+    /// including the current one, with its position, velocity and energies.
+    fn write_csv(&mut self, steps: u64, mut out: impl Write) -> io::Result<()> {
+        let axes = &self.axes;
+        let velocities = axes.iter().map(|axis| format!("v{}", axis));
+        writeln!(
+            out,
+            "time,body,{},potential,kinetic,total",
+            axes.iter().cloned().chain(velocities).join(",")
+        )?;
+
+        for step in 0..=steps {
+            if step > 0 {
+                self.step();
+            }
+
+            for body in 0..self.bodies() {
+                let values = self.positions.iter().chain(&self.velocities);
+
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{}",
+                    self.time,
+                    body,
+                    values.map(|axis| axis[body]).join(","),
+                    self.potential(body),
+                    self.kinetic(body),
+                    self.potential(body) * self.kinetic(body)
+                )?;
+            }
         }
+
+        out.flush()
     }
+}
 
-    (0..positions.len()).map(|i| {
-        let (pot, kin) = states.iter()
-            .map(|state| state[i])
-            .map(|(a, b)| (a.abs(), b.abs()))
-            .fold((0, 0), |(a, b), (c, d)| (a + c, b + d));
+impl<F: Force + Sync> NBody<F> {
+    /// Axes move independently, so each finds its own cycle on a thread of
+    /// its own. The system is in its cycle once every axis is, and comes back
+    /// round once they all do together.
+    fn cycle(&self) -> Cycle {
+        let force = &self.force;
 
-        pot * kin
-    }).sum()
+        let cycles = thread::scope(|scope| {
+            let axes = self
+                .positions
+                .iter()
+                .zip(&self.velocities)
+                .map(|(positions, velocities)| {
+                    let start = (positions.clone(), velocities.clone());
+
+                    scope.spawn(move || {
+                        find_cycle(&start, |(positions, velocities)| {
+                            advance(force, positions, velocities)
+                        })
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            axes.into_iter()
+                .map(|axis| axis.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        cycles.into_iter().fold(
+            Cycle {
+                offset: 0,
+                period: 1,
+            },
+            |a, b| Cycle {
+                offset: a.offset.max(b.offset),
+                period: lcm(a.period, b.period),
+            },
+        )
+    }
 }
 
-fn calc_period(start: &Vec<(i64, i64)>) -> u64 {
-    let mut count = 0;
-    let state = &mut start.clone();
+/// One step along a single axis.
+fn advance<F: Force>(force: &F, positions: &mut [i64], velocities: &mut [i64]) {
+    force.accelerate(positions, velocities);
+
+    for (position, velocity) in positions.iter_mut().zip(velocities.iter()) {
+        *position += velocity;
+    }
+}
 
-    loop {
-        step(state);
+/// Where a sequence of states repeats: after `offset` steps it comes back
+/// round every `period`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Cycle {
+    offset: u64,
+    period: u64,
+}
 
-        count += 1;
+/// Brent's algorithm, which only keeps two states around. The hare runs
+/// ahead in powers of two until the tortoise, left at the last power, sees it
+/// come back, which gives the period. Then a tortoise from the start and a
+/// hare a period ahead meet where the cycle begins.
+fn find_cycle<T: Clone + PartialEq>(start: &T, step: impl Fn(&mut T)) -> Cycle {
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = start.clone();
+    let mut hare = start.clone();
+    step(&mut hare);
 
-        if state == start {
-            return count;
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
         }
+
+        step(&mut hare);
+        period += 1;
+    }
+
+    let mut offset = 0;
+    let mut tortoise = start.clone();
+    let mut hare = start.clone();
+
+    for _ in 0..period {
+        step(&mut hare);
+    }
+
+    while tortoise != hare {
+        step(&mut tortoise);
+        step(&mut hare);
+        offset += 1;
+    }
+
+    Cycle { offset, period }
+}
+
+#[aoc_generator(day12)]
+fn parse_system(input: &str) -> NBody {
+    NBody::parse(input).unwrap()
+}
+
+/// Set `DAY12_CSV` to a path to save the bodies' trajectories there.
+#[aoc(day12, part1)]
+fn total_energy(system: &NBody) -> i64 {
+    if let Ok(path) = env::var("DAY12_CSV") {
+        let file = BufWriter::new(File::create(path).unwrap());
+        system.clone().write_csv(1000, file).unwrap();
     }
+
+    let mut system = system.clone();
+
+    for _ in 0..1000 {
+        system.step();
+    }
+
+    system.energy()
 }
 
+/// The first step to repeat an earlier state, which for the moons is the
+/// initial one.
 #[aoc(day12, part2)]
-fn find_period(positions: &Vec<[i64; 3]>) -> u64 {
-    let states = states_from(positions);
+fn find_period(system: &NBody) -> u64 {
+    let cycle = system.cycle();
+    cycle.offset + cycle.period
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "<x=-1, y=0, z=2>
+<x=2, y=-10, z=-7>
+<x=4, y=-8, z=8>
+<x=3, y=5, z=-1>";
+
+    #[test]
+    fn test_energy() {
+        let mut system = NBody::parse(EXAMPLE).unwrap();
+
+        for _ in 0..10 {
+            system.step();
+        }
+
+        assert_eq!(179, system.energy());
+        assert_eq!(2772, find_period(&NBody::parse(EXAMPLE).unwrap()));
+    }
 
-    lcm(
-        calc_period(&states[0]),
-        lcm(calc_period(&states[1]), calc_period(&states[2])),
-    )
+    #[test]
+    fn test_parse() {
+        let system =
+            NBody::parse("pos=<x= 2, y= 1>, vel=<x=-3, y= 2>\n<x=1, y=-8>\npos=<x=0, y=0>")
+                .unwrap();
+
+        assert_eq!(vec!["x", "y"], system.axes);
+        assert_eq!(vec![vec![2, 1, 0], vec![1, -8, 0]], system.positions);
+        assert_eq!(vec![vec![-3, 0, 0], vec![2, 0, 0]], system.velocities);
+
+        assert_eq!(
+            Err(NBodyError::Dimensions {
+                body: 1,
+                found: 3,
+                expected: 2
+            }),
+            NBody::parse("<x=1, y=2>\n<x=1, y=2, z=3>")
+        );
+        assert_eq!(
+            Err(NBodyError::Parse("<x=1 y=2>".to_string())),
+            NBody::parse("<x=1 y=2>")
+        );
+        assert_eq!(Err(NBodyError::NoAxes), NBody::parse("<>\n<>"));
+        assert_eq!(Err(NBodyError::NoBodies), NBody::parse(""));
+    }
+
+    #[test]
+    fn test_custom_force() {
+        // Bodies pushing each other apart instead
+        let repel = |a: i64, b: i64| (a - b).signum();
+        let mut system = NBody::new(
+            vec!["x".to_string()],
+            vec![(vec![0], vec![0]), (vec![1], vec![0]), (vec![5], vec![0])],
+            repel,
+        )
+        .unwrap();
+        system.step();
+
+        assert_eq!(vec![vec![-2, 1, 7]], system.positions);
+        // Only the outer two bodies are moving
+        assert_eq!(2 * 2 + 7 * 2, system.energy());
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut system = NBody::parse("<x=0, y=1>\n<x=2, y=1>").unwrap();
+        let mut out = Vec::new();
+        system.write_csv(1, &mut out).unwrap();
+
+        assert_eq!(
+            "time,body,x,y,vx,vy,potential,kinetic,total\n\
+             0,0,0,1,0,0,1,0,0\n\
+             0,1,2,1,0,0,3,0,0\n\
+             1,0,1,1,1,0,2,1,2\n\
+             1,1,1,1,-1,0,2,1,2\n",
+            String::from_utf8(out).unwrap()
+        );
+        assert_eq!(1, system.time);
+    }
+
+    #[test]
+    fn test_gravity() {
+        let positions = [3, -1, 7, 3, 0, -1, 12, 3];
+        let pairwise = |a: i64, b: i64| (b - a).signum();
+
+        let mut fast = vec![0; positions.len()];
+        let mut slow = vec![0; positions.len()];
+        Gravity.accelerate(&positions, &mut fast);
+        pairwise.accelerate(&positions, &mut slow);
+
+        assert_eq!(slow, fast);
+        assert_eq!(vec![-1, 6, -5, -1, 3, 6, -7, -1], fast);
+    }
+
+    #[derive(Debug, Copy, Clone)]
+    struct Bounce;
+
+    // Heads up until it reaches 3, then back and forth across it
+    impl Force for Bounce {
+        fn accelerate(&self, positions: &[i64], velocities: &mut [i64]) {
+            for (position, velocity) in positions.iter().zip(velocities) {
+                *velocity = if *position >= 3 { -1 } else { 1 };
+            }
+        }
+    }
+
+    #[test]
+    fn test_cycle() {
+        let more = "<x=-8, y=-10, z=0>
+<x=5, y=5, z=10>
+<x=2, y=-7, z=3>
+<x=9, y=-8, z=-3>";
+
+        assert_eq!(4_686_774_924, find_period(&NBody::parse(more).unwrap()));
+
+        // The bounce never comes back to where it started, and the first axis
+        // takes longer to settle
+        let system = NBody::new(
+            vec!["x".to_string(), "y".to_string()],
+            vec![(vec![-2, 0], vec![0, 0])],
+            Bounce,
+        )
+        .unwrap();
+
+        assert_eq!(
+            Cycle {
+                offset: 5,
+                period: 2
+            },
+            system.cycle()
+        );
+
+        let squares = find_cycle(&3, |x| *x = (*x * *x + 1) % 255);
+        let mut seen = vec![3];
+        let mut x = 3;
+
+        loop {
+            x = (x * x + 1) % 255;
+
+            if let Some(offset) = seen.iter().position(|&y| y == x) {
+                let period = seen.len() - offset;
+                assert_eq!(
+                    Cycle {
+                        offset: offset as u64,
+                        period: period as u64
+                    },
+                    squares
+                );
+                break;
+            }
+
+            seen.push(x);
+        }
+    }
 }
@@ -1,16 +1,16 @@
-use crate::intcode::computer::Computer;
+use crate::animation::{self, text_frame, Animation, Cell, Frame};
+use crate::grid::SparseGrid;
+use crate::intcode::computer::{Computer, Status};
+use crate::intcode::inspect::find_variable;
+use crate::intcode::parser::read_program;
 use aoc_runner_derive::aoc;
-use crossterm::cursor::{
-    Hide, MoveDown, MoveRight, MoveToPreviousLine, RestorePosition, SavePosition, Show,
-};
-use crossterm::style::Print;
-use crossterm::terminal::{Clear, ClearType};
-use crossterm::{ExecutableCommand, QueueableCommand};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::style::Color;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::env;
+use std::fs;
 use std::io::{stdout, Write};
-use std::sync::mpsc::channel;
-use std::thread;
 use std::time::Duration;
 
 fn char_for_tile(tile_id: i64) -> char {
@@ -24,114 +24,734 @@ fn char_for_tile(tile_id: i64) -> char {
     }
 }
 
+/// The arcade cabinet's display, built up from the `(x, y, tile)` triples
+/// the program outputs. `(-1, 0)` carries the score rather than a tile.
+#[derive(Debug, Clone, Default)]
+struct Screen {
+    tiles: SparseGrid<i64>,
+    score: i64,
+    ball: Option<(i64, i64)>,
+    paddle: Option<(i64, i64)>,
+}
+
+impl Screen {
+    fn update(&mut self, x: i64, y: i64, tile_id: i64) {
+        if x == -1 && y == 0 {
+            self.score = tile_id;
+            return;
+        }
+
+        match tile_id {
+            3 => self.paddle = Some((x, y)),
+            4 => self.ball = Some((x, y)),
+            _ => {}
+        }
+
+        self.tiles.insert((x, y), tile_id);
+    }
+
+    /// Applies a batch of output, returning the positions written to.
+    fn apply(&mut self, output: &[i64]) -> Vec<(i64, i64)> {
+        output
+            .iter()
+            .cloned()
+            .tuples()
+            .map(|(x, y, tile_id)| {
+                self.update(x, y, tile_id);
+                (x, y)
+            })
+            .collect()
+    }
+
+    fn size(&self) -> (i64, i64) {
+        self.tiles
+            .bounds()
+            .map_or((0, 0), |(_, _, max_x, max_y)| (max_x + 1, max_y + 1))
+    }
+
+    fn blocks(&self) -> usize {
+        self.tiles.iter().filter(|&(_, &tile)| tile == 2).count()
+    }
+
+    fn to_text(&self) -> String {
+        let mut text = self.tiles.render(' ', |&tile_id| char_for_tile(tile_id));
+
+        text.push_str(&format!("score: {}\n", self.score));
+        text
+    }
+}
+
+trait Renderer {
+    /// Called once per frame, after the program has drawn and before it
+    /// reads the joystick.
+    fn draw(&mut self, screen: &Screen, changed: &[(i64, i64)]);
+
+    fn finish(&mut self, _screen: &Screen) {}
+}
+
+/// Animates the game in place on the terminal, recording it when
+/// `DAY13_CAST` or `DAY13_GIF` are set.
+struct TerminalRenderer {
+    animation: Animation,
+}
+
+impl TerminalRenderer {
+    fn new() -> Self {
+        TerminalRenderer {
+            animation: Animation::new().fps(60).record_from_env("DAY13"),
+        }
+    }
+
+    fn frame(screen: &Screen) -> Frame {
+        let mut frame = text_frame(&screen.to_text());
+
+        for ((x, y), &tile_id) in screen.tiles.iter() {
+            let color = match tile_id {
+                1 => Color::DarkGrey,
+                2 => Color::Yellow,
+                3 => Color::Cyan,
+                4 => Color::White,
+                _ => continue,
+            };
+
+            if let Some(cell) = frame.get_mut((x, y)) {
+                *cell = Cell::new(char_for_tile(tile_id)).fg(color);
+            }
+        }
+
+        frame
+    }
+}
+
+impl Renderer for TerminalRenderer {
+    fn draw(&mut self, screen: &Screen, _changed: &[(i64, i64)]) {
+        self.animation.draw(&Self::frame(screen));
+    }
+
+    fn finish(&mut self, _screen: &Screen) {
+        self.animation.finish();
+    }
+}
+
+/// Writes the screen as plain text, either after every frame or only once
+/// the game is over.
+struct TextRenderer<W: Write> {
+    out: W,
+    every_frame: bool,
+}
+
+impl<W: Write> TextRenderer<W> {
+    fn new(out: W) -> Self {
+        TextRenderer {
+            out,
+            every_frame: false,
+        }
+    }
+
+    fn every_frame(out: W) -> Self {
+        TextRenderer {
+            out,
+            every_frame: true,
+        }
+    }
+}
+
+impl<W: Write> Renderer for TextRenderer<W> {
+    fn draw(&mut self, screen: &Screen, _changed: &[(i64, i64)]) {
+        if self.every_frame {
+            writeln!(self.out, "{}", screen.to_text()).unwrap();
+        }
+    }
+
+    fn finish(&mut self, screen: &Screen) {
+        if !self.every_frame {
+            write!(self.out, "{}", screen.to_text()).unwrap();
+        }
+        self.out.flush().unwrap();
+    }
+}
+
+struct NoRenderer;
+
+impl Renderer for NoRenderer {
+    fn draw(&mut self, _screen: &Screen, _changed: &[(i64, i64)]) {}
+}
+
+/// Picks a renderer from `DAY13_RENDERER` (`terminal`, `text`, `frames` or
+/// `none`). Otherwise animates on a terminal, and falls back to a final text
+/// snapshot when stdout is piped or captured or animation is turned off,
+/// unless the game is being recorded.
+fn default_renderer() -> Box<dyn Renderer> {
+    match env::var("DAY13_RENDERER").as_ref().map(String::as_str) {
+        Ok("terminal") => Box::new(TerminalRenderer::new()),
+        Ok("text") => Box::new(TextRenderer::new(stdout())),
+        Ok("frames") => Box::new(TextRenderer::every_frame(stdout())),
+        Ok("none") => Box::new(NoRenderer),
+        _ if animation::enabled() || animation::recording("DAY13") => {
+            Box::new(TerminalRenderer::new())
+        }
+        _ => Box::new(TextRenderer::new(stdout())),
+    }
+}
+
+trait JoystickStrategy {
+    /// Joystick position for the next frame: -1 for left, 1 for right, or
+    /// `None` to stop playing.
+    fn joystick(&mut self, screen: &Screen) -> Option<i64>;
+}
+
+/// Keeps the paddle under the ball.
+struct BallTracking;
+
+impl JoystickStrategy for BallTracking {
+    fn joystick(&mut self, screen: &Screen) -> Option<i64> {
+        match (screen.ball, screen.paddle) {
+            (Some((ball_x, _)), Some((paddle_x, _))) => Some((ball_x - paddle_x).signum()),
+            _ => Some(0),
+        }
+    }
+}
+
+/// Works out where the ball will come down from its last two positions and
+/// heads there ahead of it, following the ball when that can't be known.
+#[derive(Default)]
+struct Predictive {
+    previous: Option<(i64, i64)>,
+}
+
+impl Predictive {
+    /// Column where the ball will reach the row above the paddle. Blocks
+    /// break as the ball hits them, so only paths that touch nothing but walls
+    /// can be predicted.
+    fn landing(
+        screen: &Screen,
+        ball: (i64, i64),
+        velocity: (i64, i64),
+        paddle_y: i64,
+    ) -> Option<i64> {
+        let tile = |x, y| screen.tiles.get((x, y)).cloned().unwrap_or(0);
+        let ((mut x, mut y), (mut dx, mut dy)) = (ball, velocity);
+
+        if dx == 0 || dy == 0 {
+            return None;
+        }
+
+        for _ in 0..10_000 {
+            if y == paddle_y - 1 && dy > 0 {
+                return Some(x);
+            }
+
+            let around = [tile(x + dx, y), tile(x, y + dy), tile(x + dx, y + dy)];
+            if around.contains(&2) {
+                return None;
+            }
+
+            let (side, vertical) = (around[0] == 1, around[1] == 1);
+            if side {
+                dx = -dx;
+            }
+            if vertical {
+                dy = -dy;
+            }
+            if !side && !vertical && around[2] == 1 {
+                dx = -dx;
+                dy = -dy;
+            }
+
+            x += dx;
+            y += dy;
+        }
+
+        None
+    }
+}
+
+impl JoystickStrategy for Predictive {
+    fn joystick(&mut self, screen: &Screen) -> Option<i64> {
+        let (ball, paddle) = match (screen.ball, screen.paddle) {
+            (Some(ball), Some(paddle)) => (ball, paddle),
+            _ => return Some(0),
+        };
+
+        let target = self
+            .previous
+            .replace(ball)
+            .and_then(|(x, y)| Self::landing(screen, ball, (ball.0 - x, ball.1 - y), paddle.1))
+            .unwrap_or(ball.0);
+
+        Some((target - paddle.0).signum())
+    }
+}
+
+/// Steers with the arrow keys, holding still when no key is pressed. Esc or
+/// Ctrl-C stops the game, as raw mode keeps Ctrl-C from interrupting.
+struct Keyboard {
+    timeout: Duration,
+    raw: bool,
+}
+
+impl Keyboard {
+    fn new() -> Self {
+        Keyboard {
+            timeout: Duration::from_millis(100),
+            raw: false,
+        }
+    }
+}
+
+impl JoystickStrategy for Keyboard {
+    fn joystick(&mut self, _screen: &Screen) -> Option<i64> {
+        if !self.raw {
+            enable_raw_mode().unwrap();
+            self.raw = true;
+        }
+
+        let mut joystick = 0;
+        let mut timeout = self.timeout;
+
+        while event::poll(timeout).unwrap() {
+            if let Event::Key(key) = event::read().unwrap() {
+                match key.code {
+                    KeyCode::Left => joystick = -1,
+                    KeyCode::Right => joystick = 1,
+                    KeyCode::Esc => return None,
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return None
+                    }
+                    _ => {}
+                }
+            }
+
+            // Drain anything else already pressed without waiting again
+            timeout = Duration::from_millis(0);
+        }
+
+        Some(joystick)
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        if self.raw {
+            disable_raw_mode().unwrap();
+        }
+    }
+}
+
+/// Plays back recorded joystick positions, then holds still.
+struct Replay {
+    inputs: Vec<i64>,
+    next: usize,
+}
+
+impl Replay {
+    fn new(inputs: Vec<i64>) -> Self {
+        Replay { inputs, next: 0 }
+    }
+}
+
+impl JoystickStrategy for Replay {
+    fn joystick(&mut self, _screen: &Screen) -> Option<i64> {
+        let joystick = self.inputs.get(self.next).cloned().unwrap_or(0);
+        self.next += 1;
+        Some(joystick)
+    }
+}
+
+/// Picks a strategy from `DAY13_JOYSTICK` (`track`, `predict`, `keyboard` or
+/// `replay:<file>` with comma separated inputs), tracking the ball otherwise.
+fn default_strategy() -> Box<dyn JoystickStrategy> {
+    match env::var("DAY13_JOYSTICK").as_ref().map(String::as_str) {
+        Ok("predict") => Box::new(Predictive::default()),
+        Ok("keyboard") => Box::new(Keyboard::new()),
+        Ok(replay) if replay.starts_with("replay:") => {
+            let inputs = fs::read_to_string(&replay["replay:".len()..]).unwrap();
+            Box::new(Replay::new(read_program(inputs.trim()).unwrap()))
+        }
+        _ => Box::new(BallTracking),
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+struct Stats {
+    frames: usize,
+    blocks_remaining: usize,
+    /// Each change of score, with the frame it happened on.
+    scores: Vec<(usize, i64)>,
+    /// Joystick positions in the order they were read, for `Replay`.
+    inputs: Vec<i64>,
+    /// Whether the strategy stopped the game before it was over.
+    quit: bool,
+}
+
+impl Stats {
+    fn score(&self) -> i64 {
+        self.scores.last().map_or(0, |&(_, score)| score)
+    }
+}
+
 #[aoc(day13, part1)]
 fn num_block_tiles(program: &str) -> usize {
     let mut computer = Computer::load(program).unwrap();
+    let mut screen = Screen::default();
+
+    computer.resume().unwrap();
+    screen.apply(&computer.take_output());
+
+    screen.blocks()
+}
+
+fn play_with(
+    program: &str,
+    strategy: &mut dyn JoystickStrategy,
+    renderer: &mut dyn Renderer,
+) -> Stats {
+    let mut computer = Computer::load(program).unwrap();
+    let mut screen = Screen::default();
+    let mut stats = Stats::default();
+
+    computer.set(0, 2).unwrap();
+
+    loop {
+        let status = computer.resume().unwrap();
+        let changed = screen.apply(&computer.take_output());
 
-    let (sender, receiver) = channel();
+        renderer.draw(&screen, &changed);
 
-    computer.set_output(sender);
-    computer.run().unwrap();
-    drop(computer);
+        if screen.score != stats.score() {
+            stats.scores.push((stats.frames, screen.score));
+        }
 
-    let mut tiles = HashMap::new();
+        match status {
+            Status::Halted => break,
+            Status::AwaitingInput => {
+                let joystick = match strategy.joystick(&screen) {
+                    Some(joystick) => joystick,
+                    None => {
+                        stats.quit = true;
+                        break;
+                    }
+                };
 
-    for (x, y, tile_id) in receiver.iter().tuples() {
-        tiles.insert((x, y), tile_id);
+                stats.inputs.push(joystick);
+                stats.frames += 1;
+                computer.push_input(joystick);
+            }
+        }
     }
 
-    tiles.values().filter(|&&tile| tile == 2).count()
-}
+    renderer.finish(&screen);
+    stats.blocks_remaining = screen.blocks();
 
-const WIDTH: u16 = 48;
-const HEIGHT: u16 = 25;
+    stats
+}
 
+/// Set `DAY13_RECORD` to a file to save the joystick inputs for replaying,
+/// and `DAY13_STATS` to print how the game went.
 #[aoc(day13, part2)]
 fn play(program: &str) -> i64 {
-    let mut computer = Computer::load(program).unwrap();
+    let stats = play_with(
+        program,
+        default_strategy().as_mut(),
+        default_renderer().as_mut(),
+    );
 
-    computer.set(0, 2).unwrap();
+    if env::var_os("DAY13_STATS").is_some() {
+        eprintln!(
+            "frames: {}, blocks remaining: {}{}",
+            stats.frames,
+            stats.blocks_remaining,
+            if stats.quit { ", quit early" } else { "" }
+        );
+    }
 
-    let (input, receiver) = channel();
-    let (sender, output) = channel();
-    let (joy_in, joy_out) = channel();
+    if let Ok(path) = env::var("DAY13_RECORD") {
+        fs::write(path, stats.inputs.iter().join(",")).unwrap();
+    }
 
-    computer.set_input(receiver);
-    computer.set_output(sender);
+    stats.score()
+}
+
+/// Where the game keeps its state in memory, found by watching it run.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Layout {
+    board: usize,
+    width: usize,
+    height: usize,
+    ball_x: usize,
+    ball_y: usize,
+}
 
-    thread::spawn(move || computer.run().unwrap());
-    thread::spawn(move || {
-        for key in joy_out {
-            thread::sleep(Duration::from_millis(16));
-            input.send(key).unwrap();
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct GameState {
+    tiles: Vec<i64>,
+    ball: (i64, i64),
+}
+
+impl Layout {
+    fn tile(&self, memory: &[i64], x: usize, y: usize) -> i64 {
+        memory[self.board + y * self.width + x]
+    }
+
+    fn paddle_y(&self, memory: &[i64]) -> Option<usize> {
+        (0..self.height).find(|&y| (0..self.width).any(|x| self.tile(memory, x, y) == 3))
+    }
+
+    fn read(&self, memory: &[i64]) -> GameState {
+        GameState {
+            tiles: memory[self.board..self.board + self.width * self.height].to_vec(),
+            ball: (memory[self.ball_x], memory[self.ball_y]),
         }
-    });
+    }
+}
 
-    let mut stdout = stdout();
+/// Finds where the board drawn on the screen is kept, a row at a time.
+fn find_board(memory: &[i64], screen: &Screen) -> Option<usize> {
+    let (width, height) = screen.size();
+    let width = width as usize;
 
-    stdout.queue(Hide).unwrap();
+    let mut board = vec![0; width * height as usize];
+    if board.is_empty() {
+        return None;
+    }
 
-    for _ in 0..HEIGHT {
-        stdout.queue(Print('\n')).unwrap();
+    for ((x, y), &tile_id) in screen.tiles.iter() {
+        board[y as usize * width + x as usize] = tile_id;
     }
 
-    stdout
-        .queue(MoveToPreviousLine(HEIGHT))
-        .unwrap()
-        .queue(SavePosition)
-        .unwrap()
-        .flush()
-        .unwrap();
+    memory
+        .windows(board.len())
+        .position(|window| window == &board[..])
+}
+
+/// Finds the address that held each value in the snapshot taken with it.
+fn find_address(snapshots: &[Vec<i64>], values: &[i64]) -> Option<usize> {
+    find_variable(snapshots, values)
+        .into_iter()
+        .find(|candidate| candidate.matches == values.len())
+        .map(|candidate| candidate.address)
+}
+
+/// Runs the first few frames of the game, moving the paddle back and forth,
+/// and matches what is drawn against memory.
+fn locate(program: &str) -> Option<Layout> {
+    let mut computer = Computer::load(program).ok()?;
 
-    let mut score = 0;
-    let mut paddle = None;
+    computer.set(0, 2).ok()?;
+    computer.resume().ok()?;
 
-    for (x, y, tile_id) in output.iter().tuples() {
-        stdout.queue(RestorePosition).unwrap();
+    let mut screen = Screen::default();
+    screen.apply(&computer.take_output());
 
-        if tile_id == 3 && paddle.is_none() {
-            paddle = Some(x);
+    let (width, height) = screen.size();
+    let board = find_board(computer.memory(), &screen)?;
+
+    let mut snapshots = Vec::new();
+    let mut balls = Vec::new();
+
+    for frame in 0..20 {
+        snapshots.push(computer.memory().to_vec());
+        balls.push(screen.ball?);
+
+        computer.push_input(if frame % 3 == 0 { 1 } else { -1 });
+        if computer.resume().ok()? == Status::Halted {
+            break;
         }
 
-        if tile_id == 4 {
-            if let Some(paddle_x) = paddle {
-                joy_in.send((x - paddle_x).signum()).unwrap();
-                paddle = Some(x);
-            } else {
-                joy_in.send(0).unwrap();
+        screen.apply(&computer.take_output());
+    }
+
+    let xs = balls.iter().map(|&(x, _)| x).collect::<Vec<_>>();
+    let ys = balls.iter().map(|&(_, y)| y).collect::<Vec<_>>();
+
+    Some(Layout {
+        board,
+        width: width as usize,
+        height: height as usize,
+        ball_x: find_address(&snapshots, &xs)?,
+        ball_y: find_address(&snapshots, &ys)?,
+    })
+}
+
+/// Replaces the paddle's row with paddle tiles between the walls, so the
+/// ball can never be missed and no input is needed.
+fn patch(computer: &mut Computer, layout: &Layout) -> Option<()> {
+    let paddle_y = layout.paddle_y(computer.memory())?;
+
+    for x in 1..layout.width - 1 {
+        let address = layout.board + paddle_y * layout.width + x;
+        computer.set(address as i64, 3).ok()?;
+    }
+
+    Some(())
+}
+
+/// Plays a patched copy of the game to the end, checking the ball stays
+/// where it was found in memory and the score matches playing it properly.
+#[aoc(day13, part2, patched)]
+fn play_patched(program: &str) -> i64 {
+    let layout = locate(program).expect("Could not find the game in memory");
+    let mut computer = Computer::load(program).unwrap();
+
+    computer.set(0, 2).unwrap();
+    patch(&mut computer, &layout).unwrap();
+
+    let mut screen = Screen::default();
+
+    loop {
+        let status = computer.resume().unwrap();
+        screen.apply(&computer.take_output());
+
+        match status {
+            Status::Halted => break,
+            Status::AwaitingInput => {
+                let state = layout.read(computer.memory());
+                assert_eq!(
+                    screen.ball,
+                    Some(state.ball),
+                    "Ball is not where it was found"
+                );
+
+                computer.push_input(0);
             }
         }
+    }
 
-        if x == -1 && y == 0 {
-            score = tile_id;
-            stdout
-                .queue(MoveRight(WIDTH))
-                .unwrap()
-                .queue(Clear(ClearType::UntilNewLine))
-                .unwrap()
-                .queue(Print(format!("score: {}", score)))
-                .unwrap();
-        } else {
-            if x > 0 {
-                stdout.queue(MoveRight(x as u16)).unwrap();
-            }
-            if y > 0 {
-                stdout.queue(MoveDown(y as u16)).unwrap();
-            }
+    let state = layout.read(computer.memory());
+    assert!(!state.tiles.contains(&2), "Game ended with blocks left");
+
+    let played = play_with(program, &mut BallTracking, &mut NoRenderer).score();
+    assert_eq!(
+        played, screen.score,
+        "Patched game scored {} but playing scored {}",
+        screen.score, played
+    );
+
+    screen.score
+}
 
-            stdout.queue(Print(char_for_tile(tile_id))).unwrap();
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_screen() {
+        let mut screen = Screen::default();
+        let changed = screen.apply(&[0, 0, 1, 1, 0, 2, 2, 0, 1, 1, 1, 4, 1, 2, 3, -1, 0, 12]);
+
+        assert_eq!(vec![(0, 0), (1, 0), (2, 0), (1, 1), (1, 2), (-1, 0)], changed);
+        assert_eq!(Some((1, 1)), screen.ball);
+        assert_eq!(Some((1, 2)), screen.paddle);
+        assert_eq!(1, screen.blocks());
+
+        let mut out = Vec::new();
+        let mut renderer = TextRenderer::new(&mut out);
+        renderer.draw(&screen, &changed);
+        renderer.finish(&screen);
+
+        assert_eq!("%#%\n o \n - \nscore: 12\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_landing() {
+        let mut screen = Screen::default();
+        for i in 0..8 {
+            screen.update(0, i, 1);
+            screen.update(6, i, 1);
+            screen.update(i, 0, 1);
         }
 
-        stdout.flush().unwrap();
+        // Ball at (4, 2) heading down and right bounces off the right wall
+        assert_eq!(Some(3), Predictive::landing(&screen, (4, 2), (1, 1), 6));
+        // Heading up, it comes back off the top left corner
+        assert_eq!(Some(5), Predictive::landing(&screen, (2, 2), (-1, -1), 6));
+        assert_eq!(None, Predictive::landing(&screen, (2, 2), (0, 1), 6));
+
+        screen.update(4, 4, 2);
+        assert_eq!(None, Predictive::landing(&screen, (2, 2), (1, 1), 6));
     }
 
-    stdout
-        .execute(RestorePosition)
-        .unwrap()
-        .execute(MoveDown(HEIGHT))
-        .unwrap()
-        .execute(Show)
-        .unwrap();
+    fn board_screen() -> Screen {
+        let mut screen = Screen::default();
+        screen.apply(&[0, 0, 1, 1, 0, 1, 2, 0, 1, 0, 1, 1, 1, 1, 4, 2, 1, 1]);
+        screen.apply(&[0, 2, 1, 1, 2, 3, 2, 2, 1]);
+        screen
+    }
+
+    #[test]
+    fn test_find_board() {
+        let screen = board_screen();
+        let memory = vec![5, 1, 1, 1, 1, 4, 1, 1, 3, 1, 0, 0];
+
+        assert_eq!(Some(1), find_board(&memory, &screen));
+        assert_eq!(None, find_board(&memory[2..], &screen));
+        assert_eq!(None, find_board(&memory, &Screen::default()));
+    }
+
+    #[test]
+    fn test_locate_ball() {
+        // The ball is at x = address 1 and y = address 2, address 3 is always 0
+        let snapshots = vec![vec![7, 1, 1, 0], vec![7, 2, 2, 0], vec![7, 3, 1, 0]];
+
+        assert_eq!(Some(1), find_address(&snapshots, &[1, 2, 3]));
+        assert_eq!(Some(2), find_address(&snapshots, &[1, 2, 1]));
+        assert_eq!(None, find_address(&snapshots, &[0, 0, 1]));
+    }
 
-    score
+    #[test]
+    fn test_patch() {
+        let screen = board_screen();
+        let mut computer = Computer::load("99,1,1,1,1,4,1,1,3,1,2,3").unwrap();
+        let layout = Layout {
+            board: find_board(computer.memory(), &screen).unwrap(),
+            width: 3,
+            height: 3,
+            ball_x: 10,
+            ball_y: 11,
+        };
+
+        assert_eq!(Some(2), layout.paddle_y(computer.memory()));
+        assert_eq!(Some(()), patch(&mut computer, &layout));
+        assert_eq!(&[1, 3, 1], &computer.memory()[7..10]);
+        assert_eq!(
+            GameState {
+                tiles: vec![1, 1, 1, 1, 4, 1, 1, 3, 1],
+                ball: (2, 3),
+            },
+            layout.read(computer.memory())
+        );
+
+        let mut empty = Computer::load("99,1,1,1,1,0,1,1,0,1").unwrap();
+        assert_eq!(None, patch(&mut empty, &layout));
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut replay = Replay::new(vec![1, -1]);
+        let screen = Screen::default();
+
+        let inputs = (0..3).map(|_| replay.joystick(&screen)).collect::<Vec<_>>();
+        assert_eq!(vec![Some(1), Some(-1), Some(0)], inputs);
+    }
+
+    #[test]
+    fn test_quit() {
+        // Stops after a few frames, like pressing Esc at the keyboard
+        struct Quit(usize);
+
+        impl JoystickStrategy for Quit {
+            fn joystick(&mut self, _screen: &Screen) -> Option<i64> {
+                self.0 = self.0.checked_sub(1)?;
+                Some(0)
+            }
+        }
+
+        // Reads the joystick forever once started with 2 at address 0
+        let program = "1,0,0,100,3,101,1105,1,4";
+        let stats = play_with(program, &mut Quit(3), &mut NoRenderer);
+
+        assert!(stats.quit);
+        assert_eq!(3, stats.frames);
+        assert_eq!(vec![0, 0, 0], stats.inputs);
+    }
 }
@@ -1,15 +1,73 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 use nom::bytes::complete::tag;
-use nom::character::complete::{alpha1, char, digit1, line_ending};
-use nom::combinator::map_res;
+use nom::character::complete::{alpha1, char, digit1};
+use nom::combinator::{all_consuming, map_res};
 use nom::multi::separated_list;
 use nom::sequence::separated_pair;
 use nom::IResult;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fmt::{Debug, Display, Error, Formatter, Write};
+use std::fs;
+
+const ORE: &str = "ORE";
+const FUEL: &str = "FUEL";
 
 type ChemQuantity = (String, u64);
 type Equation = (Vec<ChemQuantity>, ChemQuantity);
-type ChemMap = HashMap<String, (u64, Vec<ChemQuantity>)>;
+
+#[derive(Eq, PartialEq)]
+enum ReactionError {
+    Parse(String),
+    /// More than one reaction makes the chemical.
+    Duplicate(String),
+    /// A reaction makes none of the chemical.
+    NoOutput(String),
+    /// A reaction needs a chemical nothing makes.
+    Undefined {
+        chemical: String,
+        needed_by: String,
+    },
+    Unknown(String),
+    /// Chemicals that each need the next to be made, ending where they start.
+    Cycle(Vec<String>),
+}
+
+impl Display for ReactionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            ReactionError::Parse(line) => write!(f, "Can't read a reaction from {:?}", line),
+            ReactionError::Duplicate(chemical) => {
+                write!(f, "More than one reaction makes {}", chemical)
+            }
+            ReactionError::NoOutput(chemical) => {
+                write!(f, "The reaction for {} makes none of it", chemical)
+            }
+            ReactionError::Undefined {
+                chemical,
+                needed_by,
+            } => write!(
+                f,
+                "No reaction makes {}, which {} needs",
+                chemical, needed_by
+            ),
+            ReactionError::Unknown(chemical) => write!(f, "No reaction makes {}", chemical),
+            ReactionError::Cycle(chemicals) => {
+                write!(
+                    f,
+                    "Reactions go round in a cycle: {}",
+                    chemicals.join(" <- ")
+                )
+            }
+        }
+    }
+}
+
+impl Debug for ReactionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        Display::fmt(self, f)
+    }
+}
 
 fn chemical(input: &str) -> IResult<&str, ChemQuantity> {
     let (input, num) = map_res(digit1, str::parse)(input)?;
@@ -23,71 +81,355 @@ fn equation(input: &str) -> IResult<&str, Equation> {
     separated_pair(separated_list(tag(", "), chemical), tag(" => "), chemical)(input)
 }
 
-#[aoc_generator(day14)]
-fn parse_chem_map(input: &str) -> ChemMap {
-    let (_, equations) = separated_list(line_ending, equation)(input).unwrap();
+/// Reactions that make each chemical out of others, and ultimately ore.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct ReactionGraph {
+    /// How much of a chemical a reaction makes, and what it takes.
+    reactions: BTreeMap<String, (u64, Vec<ChemQuantity>)>,
+    /// Every chemical after all the ones made from it, ending with ore.
+    order: Vec<String>,
+}
 
-    equations
-        .into_iter()
-        .map(|(inputs, output)| (output.0, (output.1, inputs)))
-        .collect()
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Mark {
+    Visiting,
+    Done,
 }
 
-fn cost_of<'a>(
-    chem: &'a str,
-    quantity: u64,
-    store: &mut HashMap<&'a str, u64>,
-    chem_map: &'a ChemMap,
-) -> u64 {
-    if chem == "ORE" {
-        quantity
-    } else {
-        let stored = store.remove(chem).unwrap_or(0);
-
-        if stored >= quantity {
-            store.insert(chem, stored - quantity);
-            0
-        } else {
-            let &(output_quantity, ref inputs) = &chem_map[chem];
-            let times = (quantity - stored + output_quantity - 1) / output_quantity;
+impl ReactionGraph {
+    /// Reads a reaction per line, checking every chemical is made by exactly
+    /// one reaction out of ore without going round in circles.
+    fn parse(input: &str) -> Result<Self, ReactionError> {
+        let mut reactions = BTreeMap::new();
+
+        for line in input.lines() {
+            let (_, (inputs, (output, quantity))) = all_consuming(equation)(line.trim())
+                .map_err(|_| ReactionError::Parse(line.to_string()))?;
+
+            if quantity == 0 {
+                return Err(ReactionError::NoOutput(output));
+            }
 
-            let extra = times * output_quantity - quantity + stored;
-            store.insert(chem, extra);
+            if reactions
+                .insert(output.clone(), (quantity, inputs))
+                .is_some()
+            {
+                return Err(ReactionError::Duplicate(output));
+            }
+        }
 
-            inputs.iter()
-                .map(|(chem, quantity)| cost_of(chem, *quantity * times, store, chem_map))
-                .sum()
+        for (output, (_, inputs)) in &reactions {
+            if let Some((chemical, _)) = inputs
+                .iter()
+                .find(|(chemical, _)| chemical != ORE && !reactions.contains_key(chemical))
+            {
+                return Err(ReactionError::Undefined {
+                    chemical: chemical.clone(),
+                    needed_by: output.clone(),
+                });
+            }
         }
+
+        let mut graph = ReactionGraph {
+            reactions,
+            order: Vec::new(),
+        };
+        graph.order = graph.topological_order()?;
+
+        Ok(graph)
+    }
+
+    /// Depth first, adding chemicals once everything they need is in.
+    fn topological_order(&self) -> Result<Vec<String>, ReactionError> {
+        let mut marks = HashMap::new();
+        let mut order = Vec::new();
+
+        for chemical in self.reactions.keys() {
+            self.visit(chemical, &mut marks, &mut Vec::new(), &mut order)?;
+        }
+
+        order.reverse();
+        Ok(order)
+    }
+
+    fn visit<'a>(
+        &'a self,
+        chemical: &'a str,
+        marks: &mut HashMap<&'a str, Mark>,
+        path: &mut Vec<&'a str>,
+        order: &mut Vec<String>,
+    ) -> Result<(), ReactionError> {
+        match marks.get(chemical) {
+            Some(Mark::Done) => return Ok(()),
+            Some(Mark::Visiting) => {
+                let start = path.iter().position(|&c| c == chemical).unwrap();
+                let mut cycle = path[start..]
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>();
+                cycle.push(chemical.to_string());
+
+                return Err(ReactionError::Cycle(cycle));
+            }
+            None => {}
+        }
+
+        marks.insert(chemical, Mark::Visiting);
+        path.push(chemical);
+
+        for (input, _) in self.inputs(chemical) {
+            self.visit(input, marks, path, order)?;
+        }
+
+        path.pop();
+        marks.insert(chemical, Mark::Done);
+        order.push(chemical.to_string());
+
+        Ok(())
+    }
+
+    fn inputs(&self, chemical: &str) -> &[ChemQuantity] {
+        self.reactions
+            .get(chemical)
+            .map_or(&[], |(_, inputs)| inputs.as_slice())
+    }
+
+    /// Chemicals from the ones made last down to ore, each before everything
+    /// it is made from.
+    fn order(&self) -> impl Iterator<Item = &str> {
+        self.order.iter().map(String::as_str)
+    }
+
+    /// Ore it takes to make `quantity` of `target`. Working down the
+    /// topological order, everything that needs a chemical has asked for it
+    /// by the time it is made, so there are never any leftovers to track.
+    fn ore_needed(&self, target: &str, quantity: u64) -> Result<u64, ReactionError> {
+        if target != ORE && !self.reactions.contains_key(target) {
+            return Err(ReactionError::Unknown(target.to_string()));
+        }
+
+        let mut needed = HashMap::new();
+        needed.insert(target, quantity);
+
+        for chemical in self.order() {
+            let (times, inputs) = match (needed.get(chemical), self.reactions.get(chemical)) {
+                (Some(&amount), Some((output, inputs))) => (amount.div_ceil(*output), inputs),
+                _ => continue,
+            };
+
+            for (input, quantity) in inputs {
+                *needed.entry(input.as_str()).or_insert(0) += quantity * times;
+            }
+        }
+
+        Ok(needed.get(ORE).cloned().unwrap_or(0))
+    }
+
+    /// Every chemical that goes into making `target`, including itself.
+    fn needed_for<'a>(&'a self, target: &'a str) -> HashSet<&'a str> {
+        let mut needed = HashSet::new();
+        let mut todo = vec![target];
+
+        while let Some(chemical) = todo.pop() {
+            if needed.insert(chemical) {
+                todo.extend(
+                    self.inputs(chemical)
+                        .iter()
+                        .map(|(input, _)| input.as_str()),
+                );
+            }
+        }
+
+        needed
+    }
+
+    /// Chemicals with a reaction that never goes into making `target`.
+    fn unreachable(&self, target: &str) -> Vec<&str> {
+        let needed = self.needed_for(target);
+
+        self.reactions
+            .keys()
+            .map(String::as_str)
+            .filter(|chemical| !needed.contains(chemical))
+            .collect()
+    }
+
+    /// The reactions as a Graphviz digraph, with an edge from each input to
+    /// what it makes labelled with how much it takes. Chemicals that don't
+    /// go into `target` are dashed.
+    fn to_dot(&self, target: &str) -> String {
+        let unreachable = self.unreachable(target);
+        let mut out = String::new();
+
+        writeln!(out, "digraph reactions {{").unwrap();
+        writeln!(out, "    {:?} [shape=box];", ORE).unwrap();
+
+        for (output, (quantity, inputs)) in &self.reactions {
+            let style = if unreachable.contains(&output.as_str()) {
+                ", style=dashed"
+            } else {
+                ""
+            };
+
+            writeln!(
+                out,
+                "    {:?} [label=\"{} {}\"{}];",
+                output, quantity, output, style
+            )
+            .unwrap();
+
+            for (input, quantity) in inputs {
+                writeln!(
+                    out,
+                    "    {:?} -> {:?} [label=\"{}\"];",
+                    input, output, quantity
+                )
+                .unwrap();
+            }
+        }
+
+        out.push_str("}\n");
+        out
     }
 }
 
+#[aoc_generator(day14)]
+fn parse_reactions(input: &str) -> ReactionGraph {
+    ReactionGraph::parse(input).unwrap()
+}
+
+/// Set `DAY14_DOT` to a path to save the reactions there as a Graphviz graph.
 #[aoc(day14, part1)]
-fn ore_cost(chem_map: &ChemMap) -> u64 {
-    cost_of("FUEL", 1, &mut HashMap::new(), chem_map)
+fn ore_cost(graph: &ReactionGraph) -> u64 {
+    if let Ok(path) = env::var("DAY14_DOT") {
+        fs::write(path, graph.to_dot(FUEL)).unwrap();
+    }
+
+    graph.ore_needed(FUEL, 1).unwrap()
 }
 
+/// Making more fuel never takes less ore, so search for the most that fits.
 #[aoc(day14, part2)]
-fn max_fuel(chem_map: &ChemMap) -> u64 {
-    let mut fuel = 1_000_000_000_000;
-    let mut count = 0;
-    let mut quantity = 1;
-
-    let mut store = HashMap::new();
-    loop {
-        let last_store = store.clone();
-
-        let cost = cost_of("FUEL", quantity, &mut store, chem_map);
-
-        if cost <= fuel {
-            fuel -= cost;
-            count += quantity;
-            quantity *= 2;
-        } else if quantity == 1 {
-            return count;
+fn max_fuel(graph: &ReactionGraph) -> u64 {
+    let ore = 1_000_000_000_000;
+    let fits = |fuel| graph.ore_needed(FUEL, fuel).unwrap() <= ore;
+
+    let one = graph.ore_needed(FUEL, 1).unwrap();
+    if one > ore {
+        return 0;
+    }
+
+    // Leftovers only help, so this much always fits
+    let mut low = ore / one;
+    let mut high = low * 2;
+
+    while fits(high) {
+        low = high;
+        high *= 2;
+    }
+
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+
+        if fits(mid) {
+            low = mid;
         } else {
-            store = last_store;
-            quantity = 1;
+            high = mid;
         }
     }
+
+    low
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "157 ORE => 5 NZVS
+165 ORE => 6 DCFZ
+44 XJWVT, 5 KHKGT, 1 QDVJ, 29 NZVS, 9 GPVTF, 48 HKGWZ => 1 FUEL
+12 HKGWZ, 1 GPVTF, 8 PSHF => 9 QDVJ
+179 ORE => 7 PSHF
+177 ORE => 5 HKGWZ
+7 DCFZ, 7 PSHF => 2 XJWVT
+165 ORE => 2 GPVTF
+3 DCFZ, 7 NZVS, 5 HKGWZ, 10 PSHF => 8 KHKGT";
+
+    #[test]
+    fn test_ore() {
+        let graph = ReactionGraph::parse(EXAMPLE).unwrap();
+
+        assert_eq!(Ok(13312), graph.ore_needed(FUEL, 1));
+        assert_eq!(82_892_753, max_fuel(&graph));
+
+        let costly = ReactionGraph::parse("1000000000001 ORE => 1 FUEL").unwrap();
+        assert_eq!(0, max_fuel(&costly));
+        let exact = ReactionGraph::parse("1000000000000 ORE => 1 FUEL").unwrap();
+        assert_eq!(1, max_fuel(&exact));
+        assert_eq!(Ok(157), graph.ore_needed("NZVS", 5));
+        assert_eq!(Ok(3), graph.ore_needed(ORE, 3));
+        assert_eq!(
+            Err(ReactionError::Unknown("GOLD".to_string())),
+            graph.ore_needed("GOLD", 1)
+        );
+    }
+
+    #[test]
+    fn test_order() {
+        let graph = ReactionGraph::parse(EXAMPLE).unwrap();
+        let order = graph.order().collect::<Vec<_>>();
+        let position = |chemical| order.iter().position(|&c| c == chemical).unwrap();
+
+        assert_eq!(Some(&FUEL), order.first());
+        assert_eq!(Some(&ORE), order.last());
+
+        for (output, (_, inputs)) in &graph.reactions {
+            for (input, _) in inputs {
+                assert!(position(output) < position(input));
+            }
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Err(ReactionError::Cycle(vec![
+                "A".to_string(),
+                "B".to_string(),
+                "A".to_string()
+            ])),
+            ReactionGraph::parse("1 B => 1 A\n1 A, 1 ORE => 1 B")
+        );
+        assert_eq!(
+            Err(ReactionError::Undefined {
+                chemical: "B".to_string(),
+                needed_by: "FUEL".to_string()
+            }),
+            ReactionGraph::parse("1 ORE => 1 A\n1 A, 2 B => 1 FUEL")
+        );
+        assert_eq!(
+            Err(ReactionError::Duplicate("A".to_string())),
+            ReactionGraph::parse("1 ORE => 1 A\n2 ORE => 1 A")
+        );
+        assert_eq!(
+            Err(ReactionError::NoOutput("A".to_string())),
+            ReactionGraph::parse("0 ORE => 0 A")
+        );
+        assert_eq!(
+            Err(ReactionError::Parse("1 ORE -> 1 A".to_string())),
+            ReactionGraph::parse("1 ORE -> 1 A")
+        );
+    }
+
+    #[test]
+    fn test_dot() {
+        let graph = ReactionGraph::parse("2 ORE => 1 A\n3 A => 1 FUEL\n1 ORE => 4 B").unwrap();
+        let dot = graph.to_dot(FUEL);
+
+        assert_eq!(vec!["B"], graph.unreachable(FUEL));
+        assert!(dot.starts_with("digraph reactions {\n"));
+        assert!(dot.contains("    \"A\" -> \"FUEL\" [label=\"3\"];\n"));
+        assert!(dot.contains("    \"B\" [label=\"4 B\", style=dashed];\n"));
+        assert!(dot.contains("    \"FUEL\" [label=\"1 FUEL\"];\n"));
+    }
+}
@@ -1,189 +1,166 @@
-use crate::intcode::computer::{Computer, RuntimeError};
+use crate::animation::{Animation, Cell, Frame};
+use crate::export::{self, Image, BLACK, CYAN, GREY, WHITE};
+use crate::geometry::Dir4;
+use crate::grid::SparseGrid;
+use crate::intcode::computer::Computer;
+use crate::intcode::explorer::{Explorer, MoveProtocol, Strategy};
 use aoc_runner_derive::aoc;
-use crossterm::style::{Colorize, Print, PrintStyledContent, Styler};
-use crossterm::QueueableCommand;
-use num::{Bounded, Num};
+use crossterm::style::Color;
 use pathfinding::directed::astar::astar;
-use std::collections::{HashMap, HashSet};
-use std::io::{stdout, Write};
-use std::sync::mpsc::{channel, Receiver, Sender};
-use std::thread;
-
-enum Direction {
-    North,
-    South,
-    West,
-    East,
+use std::collections::HashSet;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Tile {
+    Wall,
+    Open,
+    OxygenSystem,
 }
 
-impl Direction {
-    fn as_int(&self) -> i64 {
-        match self {
-            Self::North => 1,
-            Self::South => 2,
-            Self::West => 3,
-            Self::East => 4,
-        }
+struct RepairDroid;
+
+impl MoveProtocol for RepairDroid {
+    type Tile = Tile;
+
+    fn command(&self, dir: Dir4) -> i64 {
+        dir.code([Dir4::North, Dir4::South, Dir4::West, Dir4::East], 1)
     }
 
-    fn invert(&self) -> Self {
-        match self {
-            Self::North => Self::South,
-            Self::South => Self::North,
-            Self::West => Self::East,
-            Self::East => Self::West,
+    fn tile(&self, status: i64) -> Option<Tile> {
+        match status {
+            0 => Some(Tile::Wall),
+            1 => Some(Tile::Open),
+            2 => Some(Tile::OxygenSystem),
+            _ => None,
         }
     }
 
-    fn step(&self, (x, y): (i64, i64)) -> (i64, i64) {
-        match self {
-            Self::North => (x, y + 1),
-            Self::South => (x, y - 1),
-            Self::West => (x - 1, y),
-            Self::East => (x + 1, y),
-        }
+    fn passable(&self, tile: &Tile) -> bool {
+        *tile != Tile::Wall
     }
 
-    const ALL: [Direction; 4] = [Self::North, Self::South, Self::East, Self::West];
+    fn start(&self) -> Tile {
+        Tile::Open
+    }
 }
 
-fn dfs(
-    start: (i64, i64),
-    input: &Sender<i64>,
-    output: &Receiver<i64>,
-    map: &mut HashMap<(i64, i64), i64>,
-) -> Option<(i64, i64)> {
-    let mut found = None;
-
-    for dir in &Direction::ALL {
-        let next = dir.step(start);
-        if map.contains_key(&next) {
-            continue;
-        }
-
-        input.send(dir.as_int()).unwrap();
-
-        let result = output.recv().unwrap();
-        map.insert(next, result);
-
-        if result == 2 {
-            found = Some(next);
-        }
+fn exploration_frame(map: &SparseGrid<Tile>, droid: (i64, i64)) -> Frame {
+    let (min_x, min_y, max_x, max_y) = map.bounds().unwrap();
+    let mut frame = Frame::new(
+        (max_x - min_x + 1) as usize,
+        (max_y - min_y + 1) as usize,
+        Cell::default(),
+    );
 
-        if result == 1 {
-            if let Some(target) = dfs(next, input, output, map) {
-                found = Some(target);
+    for ((x, y), tile) in map.iter() {
+        let cell = if (x, y) == droid {
+            Cell::new('D').fg(Color::Cyan).bold()
+        } else {
+            match tile {
+                Tile::Wall => Cell::new('#').fg(Color::DarkGrey),
+                Tile::Open => Cell::new('.').fg(Color::DarkGrey),
+                Tile::OxygenSystem => Cell::new('X').fg(Color::White).bold(),
             }
-        }
+        };
 
-        if result != 0 {
-            input.send(dir.invert().as_int()).unwrap();
-            assert_eq!(1, output.recv().unwrap());
-        }
+        *frame.get_mut((x - min_x, y - min_y)).unwrap() = cell;
     }
 
-    found
+    frame
 }
 
-fn get_bounds<'a, T>(positions: impl Iterator<Item = &'a (T, T)>) -> (T, T, T, T)
-where
-    T: 'a + Num + Bounded + PartialOrd + Clone,
-{
-    let mut bounds = (
-        T::max_value(),
-        T::max_value(),
-        T::min_value(),
-        T::min_value(),
-    );
+/// Set `DAY15_CAST` or `DAY15_GIF` to a path to record the exploration there.
+fn explore(program: &str) -> (SparseGrid<Tile>, (i64, i64)) {
+    let computer = Computer::load(program).unwrap();
+    let mut explorer = Explorer::new(computer, RepairDroid);
 
-    for (x, y) in positions {
-        if x < &bounds.0 {
-            bounds.0 = x.clone()
-        }
-        if y < &bounds.1 {
-            bounds.1 = y.clone()
-        }
-        if x > &bounds.2 {
-            bounds.2 = x.clone()
-        }
-        if y > &bounds.3 {
-            bounds.3 = y.clone()
-        }
+    let mut animation = Animation::offline().fps(60).record_from_env("DAY15");
+    if animation.is_recording() {
+        explorer.observe(move |map, droid| animation.draw(&exploration_frame(map, droid)));
     }
 
-    bounds
-}
-
-fn start_computer(program: &str) -> (Sender<i64>, Receiver<i64>) {
-    let mut computer = Computer::load(program).unwrap();
-
-    let (input, receiver) = channel();
-    let (sender, output) = channel();
-
-    computer.set_input(receiver);
-    computer.set_output(sender);
+    explorer.explore(Strategy::NearestFrontier).unwrap();
 
-    // Run computer, ignore error from closing input stream
-    thread::spawn(move || assert_eq!(RuntimeError::InputError, computer.run().unwrap_err()));
+    let map = explorer.into_map();
+    let (found, _) = map
+        .iter()
+        .find(|&(_, &tile)| tile == Tile::OxygenSystem)
+        .unwrap();
 
-    (input, output)
+    (map, found)
 }
 
+/// Set `DAY15_EXPORT` to an image path to save the map and path there.
 #[aoc(day15, part1)]
 fn shortest_path(program: &str) -> usize {
-    let (input, output) = start_computer(program);
-    let map = &mut HashMap::new();
-    let found = dfs((0, 0), &input, &output, map).unwrap();
+    let (map, found) = explore(program);
 
     let (path, _) = astar(
         &(0, 0),
         |&point| {
-            Direction::ALL
-                .iter()
-                .map(move |dir| dir.step(point))
-                .filter(|point| map.get(&point).filter(|&&tile| tile != 0).is_some())
-                .map(|point| (point, 1))
+            map.neighbors4(point)
+                .filter(|&(_, &tile)| tile != Tile::Wall)
+                .map(|(point, _)| (point, 1))
         },
         |&(x, y)| (x - found.0).abs() + (y - found.1).abs(),
         |point| point == &found,
     )
     .unwrap();
 
-    let (min_x, min_y, max_x, max_y) = get_bounds(map.keys());
+    let (min_x, min_y, max_x, max_y) = map.bounds().unwrap();
 
-    let mut stdout = stdout();
+    let mut frame = Frame::new(
+        (max_x - min_x + 1) as usize,
+        (max_y - min_y + 1) as usize,
+        Cell::default(),
+    );
 
     for y in min_y..=max_y {
         for x in min_x..=max_x {
-            let command = if x == 0 && y == 0 {
-                PrintStyledContent("o".on_cyan().bold().white())
+            let on_path = |c| Cell::new(c).on(Color::Cyan).bold().fg(Color::White);
+
+            let cell = if x == 0 && y == 0 {
+                on_path('o')
             } else if x == found.0 && y == found.1 {
-                PrintStyledContent("X".on_cyan().bold().white())
+                on_path('X')
             } else if path.contains(&(x, y)) {
-                PrintStyledContent(".".on_cyan().bold().white())
+                on_path('.')
             } else {
-                match map.get(&(x, y)) {
-                    Some(0) => PrintStyledContent("#".dark_grey()),
-                    Some(1) => PrintStyledContent(".".dark_grey()),
-                    _ => PrintStyledContent(" ".white()),
+                match map.get((x, y)) {
+                    Some(Tile::Wall) => Cell::new('#').fg(Color::DarkGrey),
+                    Some(Tile::Open) => Cell::new('.').fg(Color::DarkGrey),
+                    _ => Cell::new(' '),
                 }
             };
 
-            stdout.queue(command).unwrap();
+            *frame.get_mut((x - min_x, y - min_y)).unwrap() = cell;
         }
-        stdout.queue(Print("\n")).unwrap();
     }
 
-    stdout.flush().unwrap();
+    let mut animation = Animation::new();
+    animation.draw(&frame);
+    animation.finish();
+
+    let mut colors = map
+        .iter()
+        .map(|(point, tile)| match tile {
+            Tile::Wall => (point, GREY),
+            _ => (point, BLACK),
+        })
+        .collect::<SparseGrid<_>>();
+    for &point in &path {
+        colors.insert(point, CYAN);
+    }
+    colors.insert(found, WHITE);
+
+    let image = Image::from_sparse(&colors, BLACK, |&color| color);
+    export::save_to_env("DAY15_EXPORT", image).unwrap();
 
     path.len() - 1
 }
 
 #[aoc(day15, part2)]
 fn minutes_to_fill(program: &str) -> u32 {
-    let (input, output) = start_computer(program);
-    let map = &mut HashMap::new();
-    let found = dfs((0, 0), &input, &output, map).unwrap();
+    let (map, found) = explore(program);
 
     let mut filled = HashSet::new();
     let mut minutes = 0;
@@ -193,14 +170,8 @@ fn minutes_to_fill(program: &str) -> u32 {
     loop {
         let mut to_fill = HashSet::new();
         for &air in &filled {
-            for dir in &Direction::ALL {
-                let neighbour = dir.step(air);
-
-                if map.get(&neighbour).cloned().unwrap_or(0) == 0 {
-                    continue
-                }
-
-                if !filled.contains(&neighbour) {
+            for (neighbour, &tile) in map.neighbors4(air) {
+                if tile != Tile::Wall && !filled.contains(&neighbour) {
                     to_fill.insert(neighbour);
                 }
             }
@@ -214,3 +185,42 @@ fn minutes_to_fill(program: &str) -> u32 {
         minutes += 1;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::explorer::maze_program;
+
+    const MAZE: &str = "\
+#########
+#D..#...#
+##.##.#.#
+#...O.#.#
+#.###...#
+#...#####
+#########";
+
+    fn program() -> String {
+        maze_program(MAZE, [Dir4::North, Dir4::South, Dir4::West, Dir4::East])
+    }
+
+    #[test]
+    fn test_explore() {
+        let (map, found) = explore(&program());
+
+        assert_eq!(found, (3, 2));
+        assert_eq!(map.get((1, 0)), Some(&Tile::Open));
+        assert_eq!(map.get((0, 1)), Some(&Tile::Wall));
+        assert_eq!(map.iter().filter(|&(_, &tile)| tile != Tile::Wall).count(), 22);
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        assert_eq!(shortest_path(&program()), 5);
+    }
+
+    #[test]
+    fn test_minutes_to_fill() {
+        assert_eq!(minutes_to_fill(&program()), 7);
+    }
+}
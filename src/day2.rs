@@ -1,26 +1,26 @@
 use aoc_runner_derive::aoc;
-use crate::intcode::computer::Computer;
+use crate::intcode::oracle::Oracle;
 
-fn execute(program: &str, a: i64, b: i64) -> i64 {
-    let mut computer = Computer::load(program).unwrap();
-
-    computer.set(1, a).unwrap();
-    computer.set(2, b).unwrap();
-    computer.run().unwrap();
+fn load(program: &str) -> Oracle {
+    Oracle::load_patched(program, &[1, 2], 0).unwrap()
+}
 
-    computer.get(0).unwrap()
+fn execute(oracle: &Oracle, a: i64, b: i64) -> i64 {
+    oracle.evaluate(&[a, b]).unwrap()[0]
 }
 
 #[aoc(day2, part1)]
 fn run_program(program: &str) -> i64 {
-    execute(program, 12, 2)
+    execute(&load(program), 12, 2)
 }
 
 #[aoc(day2, part2)]
 fn find_values(program: &str) -> i64 {
+    let oracle = load(program);
+
     for i in 0..=99 {
         for j in 0..=99 {
-            if execute(program, i, j) == 19690720 {
+            if execute(&oracle, i, j) == 19690720 {
                 return 100 * i + j
             }
         }
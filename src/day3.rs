@@ -1,44 +1,25 @@
 use aoc_runner_derive::{aoc, aoc_generator};
+use crate::geometry::Dir4;
 use nom::IResult;
-use nom::branch::alt;
-use nom::combinator::{map_res, all_consuming, opt};
+use nom::combinator::{map_res, map_opt, all_consuming, opt};
 use nom::bytes::complete::tag;
-use nom::character::complete::{digit1, line_ending};
+use nom::character::complete::{digit1, line_ending, one_of};
 use nom::multi::separated_list;
 use std::collections::BTreeMap;
 use std::ops::Bound;
 
 #[derive(Debug, Eq, PartialEq)]
-enum Move {
-    Up(i32),
-    Down(i32),
-    Left(i32),
-    Right(i32)
-}
+struct Move(Dir4, i32);
 
 fn number(input: &str) -> IResult<&str, i32> {
     map_res(digit1, str::parse)(input)
 }
 
 fn parse_move(input: &str) -> IResult<&str, Move> {
-    let (input, dir) = alt((
-        tag("U"),
-        tag("D"),
-        tag("L"),
-        tag("R")
-    ))(input)?;
-
+    let (input, dir) = map_opt(one_of("UDLR"), Dir4::from_char)(input)?;
     let (input, num) = number(input)?;
 
-    let m = match dir {
-        "U" => Move::Up(num),
-        "D" => Move::Down(num),
-        "L" => Move::Left(num),
-        "R" => Move::Right(num),
-        _ => unreachable!()
-    };
-
-    Ok((input, m))
+    Ok((input, Move(dir, num)))
 }
 
 fn parse_path(input: &str) -> IResult<&str, Vec<Move>> {
@@ -91,59 +72,39 @@ fn create_events(path: &[Move], h: &mut Vec<Event>, v: &mut Vec<Event>) {
     let mut cur = (0, 0);
     let mut dist = 0;
 
-    for m in path {
-        match m {
-            &Move::Up(i) => {
-                v.push(Event::VLine {
-                    x: cur.0,
-                    y1: cur.1,
-                    y2: cur.1 + i,
-                    dist
-                });
-                cur.1 += i;
-                dist += i;
-            },
-            &Move::Down(i) => {
+    for &Move(dir, i) in path {
+        let offset = dir.offset();
+        let (dx, dy) = (offset.x as i32 * i, offset.y as i32 * i);
+
+        match dir {
+            Dir4::North | Dir4::South => {
                 v.push(Event::VLine {
                     x: cur.0,
                     y1: cur.1,
-                    y2: cur.1 - i,
+                    y2: cur.1 + dy,
                     dist
                 });
-                cur.1 -= i;
-                dist += i;
             },
-            &Move::Left(i) => {
-                cur.0 -= i;
-                dist += i;
+            Dir4::East | Dir4::West => {
+                // Horizontal lines always run from their left end
+                let start = cur.0.min(cur.0 + dx);
                 h.push(Event::HStart {
-                    x: cur.0,
-                    y: cur.1,
-                    dist,
-                    dir: -1
-                });
-                h.push(Event::HEnd {
-                    x: cur.0 + i,
-                    x_orig: cur.0,
-                    y: cur.1
-                });
-            },
-            &Move::Right(i) => {
-                h.push(Event::HStart {
-                    x: cur.0,
+                    x: start,
                     y: cur.1,
-                    dist,
-                    dir: 1
+                    dist: dist + (cur.0 - start),
+                    dir: offset.x as i32
                 });
                 h.push(Event::HEnd {
-                    x: cur.0 + i,
-                    x_orig: cur.0,
+                    x: start + i,
+                    x_orig: start,
                     y: cur.1
                 });
-                cur.0 += i;
-                dist += i;
             }
         }
+
+        cur.0 += dx;
+        cur.1 += dy;
+        dist += i;
     }
 }
 
@@ -259,17 +220,17 @@ mod test {
         let (path1, path2) = parse_paths(test_data);
 
         assert_eq!(path1, vec![
-            Move::Right(8),
-            Move::Up(5),
-            Move::Left(5),
-            Move::Down(3)
+            Move(Dir4::East, 8),
+            Move(Dir4::North, 5),
+            Move(Dir4::West, 5),
+            Move(Dir4::South, 3)
         ]);
 
         assert_eq!(path2, vec![
-            Move::Up(7),
-            Move::Right(6),
-            Move::Down(4),
-            Move::Left(4)
+            Move(Dir4::North, 7),
+            Move(Dir4::East, 6),
+            Move(Dir4::South, 4),
+            Move(Dir4::West, 4)
         ]);
     }
 
@@ -284,13 +245,13 @@ mod test {
         assert_eq!(h, vec![
             Event::HStart{x: 0, y: 0, dist: 0, dir: 1},
             Event::HEnd{x: 8, x_orig: 0, y: 0},
-            Event::HStart{x: 3, y: 5, dist: 18, dir: -1},
-            Event::HEnd{x: 8, x_orig: 3, y: 5}
+            Event::HStart{x: 3, y: -5, dist: 18, dir: -1},
+            Event::HEnd{x: 8, x_orig: 3, y: -5}
         ]);
 
         assert_eq!(v, vec![
-            Event::VLine{x: 8, y1: 0, y2: 5, dist: 8},
-            Event::VLine{x: 3, y1: 5, y2: 2, dist: 18}
+            Event::VLine{x: 8, y1: 0, y2: -5, dist: 8},
+            Event::VLine{x: 3, y1: -5, y2: -2, dist: 18}
         ]);
 
         h.sort_by_key(Event::get_x);
@@ -298,14 +259,14 @@ mod test {
 
         assert_eq!(h, vec![
             Event::HStart{x: 0, y: 0, dist: 0, dir: 1},
-            Event::HStart{x: 3, y: 5, dist: 18, dir: -1},
+            Event::HStart{x: 3, y: -5, dist: 18, dir: -1},
             Event::HEnd{x: 8, x_orig: 0, y: 0},
-            Event::HEnd{x: 8, x_orig: 3, y: 5}
+            Event::HEnd{x: 8, x_orig: 3, y: -5}
         ]);
 
         assert_eq!(v, vec![
-            Event::VLine{x: 3, y1: 5, y2: 2, dist: 18},
-            Event::VLine{x: 8, y1: 0, y2: 5, dist: 8}
+            Event::VLine{x: 3, y1: -5, y2: -2, dist: 18},
+            Event::VLine{x: 8, y1: 0, y2: -5, dist: 8}
         ]);
     }
 
@@ -1,48 +1,47 @@
 use aoc_runner_derive::aoc;
+use crate::intcode::asynchronous::{channel, Executor};
 use crate::intcode::computer::Computer;
 use itertools::Itertools;
-use std::sync::mpsc::channel;
 use std::iter::repeat_with;
-use std::thread;
-use std::thread::JoinHandle;
 
 const NUM_COMPUTERS: usize = 5;
 
 fn max_signal(program: &str, phases: impl Iterator<Item=i64>) -> i64 {
     phases.permutations(NUM_COMPUTERS)
         .map(|phases| {
-            let mut computers = repeat_with(|| Computer::load(program).unwrap())
+            let mut executor = Executor::new();
+
+            let (mut senders, receivers): (Vec<_>, Vec<_>) = repeat_with(channel)
                 .take(NUM_COMPUTERS)
-                .collect_vec();
+                .unzip();
 
-            for i in 0..NUM_COMPUTERS {
-                let (sender, receiver) = channel();
+            for (sender, &phase) in senders.iter().zip(&phases) {
+                sender.send(phase).unwrap();
+            }
 
-                sender.send(phases[i]).unwrap();
+            senders[0].send(0).unwrap();
 
-                computers[i].set_output(sender);
-                computers[(i + 1) % NUM_COMPUTERS].set_input(receiver);
-            }
+            // Each computer outputs to the next one's input
+            senders.rotate_left(1);
+
+            let handles = receivers.into_iter()
+                .zip(senders)
+                .map(|(input, output)| {
+                    let mut computer = Computer::load(program).unwrap();
+
+                    executor.spawn(async move {
+                        computer.run_async(&input, &output).await.unwrap();
+                        input
+                    })
+                })
+                .collect_vec();
+
+            assert_eq!(0, executor.run());
 
-            computers
-                .last().unwrap()
-                .clone_output().unwrap()
-                .send(0).unwrap();
-
-            let inputs = computers.into_iter()
-                .map(|mut computer| thread::spawn(move || {
-                    computer.run().unwrap();
-                    computer.take_input().unwrap()
-                }))
-                .collect_vec()
-                .into_iter()
-                .map(JoinHandle::join)
-                .collect::<Result<Vec<_>, _>>()
-                .unwrap();
-
-            inputs
+            handles
                 .first().unwrap()
-                .recv().unwrap()
+                .take().unwrap()
+                .try_recv().unwrap()
         })
         .max()
         .unwrap()
@@ -1,47 +1,39 @@
 use aoc_runner_derive::aoc;
+use crate::export::{self, Image, BLACK, WHITE};
+use crate::ocr;
+use crate::sif::SifImage;
 
 const IMAGE_WIDTH: usize = 25;
 const IMAGE_HEIGHT: usize = 6;
-const LAYER_SIZE: usize = IMAGE_WIDTH * IMAGE_HEIGHT;
-
-fn count_digits(line: &[u8], digit: char) -> usize {
-    line.iter().filter(|&&c| c == digit as u8).count()
-}
+const TRANSPARENT: u8 = 2;
 
 #[aoc(day8, part1)]
-fn validate_image(image: &[u8]) -> usize {
-    let line = image.chunks_exact(LAYER_SIZE)
-        .min_by_key(|line| count_digits(line, '0')).unwrap();
+fn validate_image(data: &[u8]) -> usize {
+    let image = SifImage::decode(data, IMAGE_WIDTH, IMAGE_HEIGHT).unwrap();
 
-    count_digits(line, '1' ) * count_digits(line, '2')
+    let counts = (0..image.layers().count())
+        .map(|index| image.histogram(index).unwrap())
+        .min_by_key(|counts| counts[0])
+        .unwrap();
+
+    counts[1] * counts[2]
 }
 
+/// Set `DAY8_EXPORT` to an image path to save the message there too.
 #[aoc(day8, part2)]
-fn print_image(layers: &[u8]) -> &'static str {
-    let mut image = ['2' as u8; LAYER_SIZE];
-
-    for layer in layers.chunks_exact(LAYER_SIZE) {
-        for i in 0..LAYER_SIZE {
-            if image[i] == '2' as u8 {
-                image[i] = layer[i];
-            }
-        }
-    }
-
-    for line in image.chunks_exact(IMAGE_WIDTH) {
-        let line = line.iter()
-            .map(|&pixel| {
-                match pixel as char {
-                    '1' => 'X',
-                    '0' => ' ',
-                    e => panic!("Non black or white pixel {}", e)
-                }
-            })
-            .collect::<String>();
-
-        println!("{}", line);
-    }
-    println!();
-
-    ""
+fn print_image(data: &[u8]) -> String {
+    let image = SifImage::decode(data, IMAGE_WIDTH, IMAGE_HEIGHT).unwrap();
+
+    let pixels = image.composite(TRANSPARENT).map(|&pixel| match pixel {
+        1 => true,
+        0 => false,
+        e => panic!("Non black or white pixel {}", e),
+    });
+
+    println!("{}", pixels.render(|&on| if on { 'X' } else { ' ' }));
+
+    let image = Image::from_grid(&pixels, |&on| if on { WHITE } else { BLACK });
+    export::save_to_env("DAY8_EXPORT", image).unwrap();
+
+    ocr::recognize(&pixels.rows().collect::<Vec<_>>()).unwrap()
 }
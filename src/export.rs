@@ -0,0 +1,363 @@
+use crate::grid::{Grid, SparseGrid};
+use std::env;
+use std::fmt::{Debug, Display, Error, Formatter, Write};
+use std::fs;
+use std::path::Path;
+
+/// A color as red, green and blue.
+pub type Rgb = [u8; 3];
+
+pub const BLACK: Rgb = [0, 0, 0];
+pub const WHITE: Rgb = [255, 255, 255];
+pub const GREY: Rgb = [128, 128, 128];
+pub const CYAN: Rgb = [0, 170, 170];
+pub const RED: Rgb = [205, 0, 0];
+
+#[derive(Eq, PartialEq)]
+pub enum ExportError {
+    Io(String),
+    UnknownFormat(String),
+}
+
+impl Display for ExportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            ExportError::Io(error) => write!(f, "Error writing image: {}", error),
+            ExportError::UnknownFormat(path) => write!(
+                f,
+                "Can't tell the image format of {}, expected .ppm, .png or .svg",
+                path
+            ),
+        }
+    }
+}
+
+impl Debug for ExportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        Display::fmt(self, f)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    Ppm,
+    Png,
+    Svg,
+}
+
+impl Format {
+    /// Picks the format from a file extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Format> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "ppm" => Some(Format::Ppm),
+            "png" => Some(Format::Png),
+            "svg" => Some(Format::Svg),
+            _ => None,
+        }
+    }
+}
+
+/// A grid colored in through a palette, ready to be written out. Every cell
+/// becomes a `scale` by `scale` square of pixels.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb>,
+    scale: usize,
+}
+
+impl Image {
+    pub fn from_grid<T>(grid: &Grid<T>, palette: impl Fn(&T) -> Rgb) -> Self {
+        Image {
+            width: grid.width(),
+            height: grid.height(),
+            pixels: grid.iter().map(|(_, cell)| palette(cell)).collect(),
+            scale: 1,
+        }
+    }
+
+    /// Colors the bounds of a sparse grid, with `background` where there is
+    /// no cell. Rows run down the image in increasing `y`.
+    pub fn from_sparse<T>(
+        grid: &SparseGrid<T>,
+        background: Rgb,
+        palette: impl Fn(&T) -> Rgb,
+    ) -> Self {
+        let (min_x, min_y, max_x, max_y) = grid.bounds().unwrap_or((0, 0, -1, -1));
+
+        Image {
+            width: (max_x - min_x + 1) as usize,
+            height: (max_y - min_y + 1) as usize,
+            pixels: (min_y..=max_y)
+                .flat_map(|y| grid.row(y))
+                .map(|cell| cell.map_or(background, &palette))
+                .collect(),
+            scale: 1,
+        }
+    }
+
+    pub fn scale(mut self, scale: usize) -> Self {
+        self.scale = scale.max(1);
+        self
+    }
+
+    /// Size in output pixels, after scaling.
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.width * self.scale, self.height * self.scale)
+    }
+
+    fn scaled_rows(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let scale = self.scale;
+
+        self.pixels
+            .chunks(self.width.max(1))
+            .take(self.height)
+            .flat_map(move |row| {
+                let line = row
+                    .iter()
+                    .flat_map(|pixel| (0..scale).flat_map(move |_| pixel.iter().cloned()))
+                    .collect::<Vec<_>>();
+
+                (0..scale).map(move |_| line.clone())
+            })
+    }
+
+    /// Binary PPM, which almost anything can read.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let (width, height) = self.dimensions();
+        let mut out = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+
+        for row in self.scaled_rows() {
+            out.extend(row);
+        }
+
+        out
+    }
+
+    pub fn to_png(&self) -> Vec<u8> {
+        let (width, height) = self.dimensions();
+
+        let mut header = Vec::new();
+        header.extend(&(width as u32).to_be_bytes());
+        header.extend(&(height as u32).to_be_bytes());
+        // 8 bits per channel, RGB, default compression, filtering and no interlacing
+        header.extend(&[8, 2, 0, 0, 0]);
+
+        // Each scanline starts with its filter type, none
+        let mut raw = Vec::new();
+        for row in self.scaled_rows() {
+            raw.push(0);
+            raw.extend(row);
+        }
+
+        let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+        png_chunk(&mut out, b"IHDR", &header);
+        png_chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+        png_chunk(&mut out, b"IEND", &[]);
+
+        out
+    }
+
+    /// One rectangle per horizontal run of a color.
+    pub fn to_svg(&self) -> String {
+        let (width, height) = self.dimensions();
+        let mut out = String::new();
+
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" shape-rendering="crispEdges">"#,
+            width, height
+        )
+        .unwrap();
+
+        for (y, row) in self.pixels.chunks(self.width.max(1)).enumerate() {
+            let mut x = 0;
+
+            while x < row.len() {
+                let color = row[x];
+                let run = row[x..].iter().take_while(|&&c| c == color).count();
+
+                writeln!(
+                    out,
+                    r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#{:02x}{:02x}{:02x}"/>"##,
+                    x * self.scale,
+                    y * self.scale,
+                    run * self.scale,
+                    self.scale,
+                    color[0],
+                    color[1],
+                    color[2]
+                )
+                .unwrap();
+
+                x += run;
+            }
+        }
+
+        out.push_str("</svg>\n");
+        out
+    }
+
+    pub fn encode(&self, format: Format) -> Vec<u8> {
+        match format {
+            Format::Ppm => self.to_ppm(),
+            Format::Png => self.to_png(),
+            Format::Svg => self.to_svg().into_bytes(),
+        }
+    }
+
+    /// Writes the image in the format its extension names.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ExportError> {
+        let path = path.as_ref();
+        let format = Format::from_path(path)
+            .ok_or_else(|| ExportError::UnknownFormat(path.display().to_string()))?;
+
+        fs::write(path, self.encode(format)).map_err(|e| ExportError::Io(e.to_string()))
+    }
+}
+
+/// Saves the image to the path in the environment variable `var`, if it is
+/// set, scaled up by `EXPORT_SCALE` when that is set too.
+pub fn save_to_env(var: &str, image: Image) -> Result<(), ExportError> {
+    let path = match env::var(var) {
+        Ok(path) => path,
+        Err(_) => return Ok(()),
+    };
+
+    image.scale(env_scale().unwrap_or(1)).save(path)
+}
+
+/// The scale set in `EXPORT_SCALE`, if any.
+pub fn env_scale() -> Option<usize> {
+    env::var("EXPORT_SCALE")
+        .ok()
+        .and_then(|scale| scale.parse().ok())
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend(&(data.len() as u32).to_be_bytes());
+
+    let start = out.len();
+    out.extend(kind);
+    out.extend(data);
+
+    let crc = crc32(&out[start..]);
+    out.extend(&crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+
+    for &byte in bytes {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+
+    (b << 16) | a
+}
+
+// Deflate without compression, the images are small enough not to care
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+
+    if blocks.peek().is_none() {
+        out.extend(&[1, 0, 0, 0xff, 0xff]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let len = block.len() as u16;
+
+        out.push(last as u8);
+        out.extend(&len.to_le_bytes());
+        out.extend(&(!len).to_le_bytes());
+        out.extend(block);
+    }
+
+    out.extend(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn checkerboard() -> Image {
+        let grid = Grid::from_rows(vec![vec![true, false], vec![false, true]]).unwrap();
+        Image::from_grid(&grid, |&on| if on { WHITE } else { BLACK })
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(0xae42_6082, crc32(b"IEND"));
+        assert_eq!(0x11e6_0398, adler32(b"Wikipedia"));
+    }
+
+    #[test]
+    fn test_ppm() {
+        let ppm = checkerboard().scale(2).to_ppm();
+        let header = b"P6\n4 4\n255\n";
+
+        assert_eq!(header, &ppm[..header.len()]);
+        assert_eq!(header.len() + 4 * 4 * 3, ppm.len());
+        // Second row repeats the first, which is two white then two black
+        assert_eq!(&[255; 6], &ppm[header.len() + 12..header.len() + 18]);
+        assert_eq!(&[0; 6], &ppm[header.len() + 18..header.len() + 24]);
+    }
+
+    #[test]
+    fn test_png() {
+        let png = checkerboard().to_png();
+
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &png[..8]);
+        assert_eq!(b"IHDR", &png[12..16]);
+        assert_eq!(&[0, 0, 0, 2, 0, 0, 0, 2, 8, 2], &png[16..26]);
+        assert_eq!(b"IEND\xae\x42\x60\x82", &png[png.len() - 8..]);
+
+        // Filter byte and two pixels per row, stored in a single block
+        let raw = [0, 255, 255, 255, 0, 0, 0, 0, 0, 0, 0, 255, 255, 255];
+        let idat = &png[37..png.len() - 12];
+        assert_eq!(b"IDAT", &idat[..4]);
+        assert_eq!(&raw[..], &idat[4 + 7..4 + 7 + raw.len()]);
+    }
+
+    #[test]
+    fn test_svg() {
+        let mut grid = SparseGrid::new();
+        grid.insert((1, 5), 1);
+        grid.insert((3, 5), 1);
+        grid.insert((1, 6), 2);
+
+        let palette = [BLACK, WHITE, GREY];
+        let svg = Image::from_sparse(&grid, CYAN, |&c| palette[c])
+            .scale(10)
+            .to_svg();
+
+        assert!(svg.contains(r#"width="30" height="20""#));
+        assert!(svg.contains(r##"<rect x="10" y="0" width="10" height="10" fill="#00aaaa"/>"##));
+        assert!(svg.contains(r##"<rect x="10" y="10" width="20" height="10" fill="#00aaaa"/>"##));
+        assert_eq!(5, svg.matches("<rect").count());
+        assert_eq!(Some(Format::Svg), Format::from_path("hull.SVG"));
+    }
+}
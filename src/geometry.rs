@@ -0,0 +1,307 @@
+//! Points and compass directions. North is up, towards negative `y`, the
+//! same way up as `grid` with its origin at the top left.
+
+use std::convert::TryFrom;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Point2 {
+    pub x: i64,
+    pub y: i64,
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Point3 {
+    pub x: i64,
+    pub y: i64,
+    pub z: i64,
+}
+
+// Componentwise arithmetic, and scaling by an integer
+macro_rules! point_ops {
+    ($point:ident { $($axis:ident),+ }) => {
+        impl Add for $point {
+            type Output = $point;
+
+            fn add(self, other: $point) -> $point {
+                $point { $($axis: self.$axis + other.$axis),+ }
+            }
+        }
+
+        impl Sub for $point {
+            type Output = $point;
+
+            fn sub(self, other: $point) -> $point {
+                $point { $($axis: self.$axis - other.$axis),+ }
+            }
+        }
+
+        impl Neg for $point {
+            type Output = $point;
+
+            fn neg(self) -> $point {
+                $point { $($axis: -self.$axis),+ }
+            }
+        }
+
+        impl Mul<i64> for $point {
+            type Output = $point;
+
+            fn mul(self, factor: i64) -> $point {
+                $point { $($axis: self.$axis * factor),+ }
+            }
+        }
+
+        impl AddAssign for $point {
+            fn add_assign(&mut self, other: $point) {
+                *self = *self + other;
+            }
+        }
+
+        impl SubAssign for $point {
+            fn sub_assign(&mut self, other: $point) {
+                *self = *self - other;
+            }
+        }
+
+        impl $point {
+            pub fn manhattan(self, other: $point) -> i64 {
+                0 $(+ (self.$axis - other.$axis).abs())+
+            }
+        }
+    };
+}
+
+point_ops!(Point2 { x, y });
+point_ops!(Point3 { x, y, z });
+
+impl Point2 {
+    pub const ORIGIN: Point2 = Point2 { x: 0, y: 0 };
+
+    pub fn new(x: i64, y: i64) -> Self {
+        Point2 { x, y }
+    }
+
+    pub fn step(self, dir: impl Into<Dir8>) -> Point2 {
+        self + dir.into().offset()
+    }
+}
+
+impl Point3 {
+    pub const ORIGIN: Point3 = Point3 { x: 0, y: 0, z: 0 };
+
+    pub fn new(x: i64, y: i64, z: i64) -> Self {
+        Point3 { x, y, z }
+    }
+}
+
+impl From<(i64, i64)> for Point2 {
+    fn from((x, y): (i64, i64)) -> Self {
+        Point2 { x, y }
+    }
+}
+
+impl From<Point2> for (i64, i64) {
+    fn from(point: Point2) -> Self {
+        (point.x, point.y)
+    }
+}
+
+impl From<[i64; 3]> for Point3 {
+    fn from([x, y, z]: [i64; 3]) -> Self {
+        Point3 { x, y, z }
+    }
+}
+
+impl From<Point3> for [i64; 3] {
+    fn from(point: Point3) -> Self {
+        [point.x, point.y, point.z]
+    }
+}
+
+/// The four compass directions, with north up towards negative `y`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Dir4 {
+    North,
+    East,
+    South,
+    West,
+}
+
+/// Compass directions including the diagonals, with north up towards negative `y`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Dir8 {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl Dir4 {
+    /// Clockwise from north.
+    pub const ALL: [Dir4; 4] = [Dir4::North, Dir4::East, Dir4::South, Dir4::West];
+
+    fn index(self) -> i64 {
+        self as i64
+    }
+
+    /// Turns clockwise by a number of quarter turns, negative for anticlockwise.
+    pub fn rotate(self, quarters: i64) -> Dir4 {
+        Dir4::ALL[(self.index() + quarters).rem_euclid(4) as usize]
+    }
+
+    pub fn turn_left(self) -> Dir4 {
+        self.rotate(-1)
+    }
+
+    pub fn turn_right(self) -> Dir4 {
+        self.rotate(1)
+    }
+
+    pub fn invert(self) -> Dir4 {
+        self.rotate(2)
+    }
+
+    pub fn offset(self) -> Point2 {
+        Dir8::from(self).offset()
+    }
+
+    /// Reads `N`/`E`/`S`/`W` or `U`/`R`/`D`/`L`.
+    pub fn from_char(c: char) -> Option<Dir4> {
+        match c {
+            'N' | 'U' => Some(Dir4::North),
+            'E' | 'R' => Some(Dir4::East),
+            'S' | 'D' => Some(Dir4::South),
+            'W' | 'L' => Some(Dir4::West),
+            _ => None,
+        }
+    }
+
+    /// The number a protocol uses for this direction, when it numbers the
+    /// directions in `order` counting up from `first`.
+    pub fn code(self, order: [Dir4; 4], first: i64) -> i64 {
+        first + order.iter().position(|&dir| dir == self).unwrap() as i64
+    }
+
+    pub fn from_code(code: i64, order: [Dir4; 4], first: i64) -> Option<Dir4> {
+        order.get(usize::try_from(code - first).ok()?).cloned()
+    }
+}
+
+impl Dir8 {
+    /// Clockwise from north.
+    pub const ALL: [Dir8; 8] = [
+        Dir8::North,
+        Dir8::NorthEast,
+        Dir8::East,
+        Dir8::SouthEast,
+        Dir8::South,
+        Dir8::SouthWest,
+        Dir8::West,
+        Dir8::NorthWest,
+    ];
+
+    fn index(self) -> i64 {
+        self as i64
+    }
+
+    /// Turns clockwise by a number of eighth turns, negative for anticlockwise.
+    pub fn rotate(self, eighths: i64) -> Dir8 {
+        Dir8::ALL[(self.index() + eighths).rem_euclid(8) as usize]
+    }
+
+    pub fn turn_left(self) -> Dir8 {
+        self.rotate(-1)
+    }
+
+    pub fn turn_right(self) -> Dir8 {
+        self.rotate(1)
+    }
+
+    pub fn invert(self) -> Dir8 {
+        self.rotate(4)
+    }
+
+    pub fn offset(self) -> Point2 {
+        let (x, y) = match self {
+            Dir8::North => (0, -1),
+            Dir8::NorthEast => (1, -1),
+            Dir8::East => (1, 0),
+            Dir8::SouthEast => (1, 1),
+            Dir8::South => (0, 1),
+            Dir8::SouthWest => (-1, 1),
+            Dir8::West => (-1, 0),
+            Dir8::NorthWest => (-1, -1),
+        };
+
+        Point2 { x, y }
+    }
+
+    /// The number a protocol uses for this direction, when it numbers the
+    /// directions in `order` counting up from `first`.
+    pub fn code(self, order: [Dir8; 8], first: i64) -> i64 {
+        first + order.iter().position(|&dir| dir == self).unwrap() as i64
+    }
+
+    pub fn from_code(code: i64, order: [Dir8; 8], first: i64) -> Option<Dir8> {
+        order.get(usize::try_from(code - first).ok()?).cloned()
+    }
+}
+
+impl From<Dir4> for Dir8 {
+    fn from(dir: Dir4) -> Self {
+        Dir8::ALL[dir.index() as usize * 2]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grid;
+
+    #[test]
+    fn test_points() {
+        let a = Point2::new(1, -2);
+        let b = Point2::new(-3, 4);
+
+        assert_eq!(Point2::new(-2, 2), a + b);
+        assert_eq!(Point2::new(4, -6), a - b);
+        assert_eq!(Point2::new(3, -6), a * 3);
+        assert_eq!(10, a.manhattan(b));
+        assert_eq!(Point2::new(1, -3), a.step(Dir4::North));
+        assert_eq!(Point2::new(0, -1), a.step(Dir8::SouthWest));
+        assert_eq!(
+            grid::NEIGHBORS8.to_vec(),
+            Dir8::ALL
+                .iter()
+                .map(|dir| dir.offset().into())
+                .collect::<Vec<(i64, i64)>>()
+        );
+
+        let mut c = Point3::new(1, 2, 3);
+        c -= Point3::new(2, 2, 2);
+        assert_eq!(Point3::new(-1, 0, 1), c);
+        assert_eq!(2, c.manhattan(Point3::ORIGIN));
+        assert_eq!([1, 0, -1], <[i64; 3]>::from(-c));
+    }
+
+    #[test]
+    fn test_directions() {
+        assert_eq!(Dir4::West, Dir4::North.turn_left());
+        assert_eq!(Dir4::North, Dir4::West.rotate(5));
+        assert_eq!(Dir4::South, Dir4::North.invert());
+        assert_eq!(Dir8::NorthWest, Dir8::North.turn_left());
+        assert_eq!(Dir8::SouthEast, Dir8::NorthWest.invert());
+        assert_eq!(Dir8::East, Dir4::East.into());
+
+        let order = [Dir4::North, Dir4::South, Dir4::West, Dir4::East];
+        assert_eq!(3, Dir4::West.code(order, 1));
+        assert_eq!(Some(Dir4::East), Dir4::from_code(4, order, 1));
+        assert_eq!(None, Dir4::from_code(0, order, 1));
+        assert_eq!(Some(Dir4::West), Dir4::from_char('L'));
+    }
+}
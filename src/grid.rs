@@ -0,0 +1,431 @@
+//! Dense and sparse 2D grids. The origin is at the top left with `y` growing
+//! downwards, so up is negative `y`, as for `geometry`'s directions.
+
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Error, Formatter};
+use std::iter::FromIterator;
+
+/// Offsets to the orthogonal neighbours, clockwise from up (negative `y`).
+pub const NEIGHBORS4: [(i64, i64); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+/// Offsets to the orthogonal and diagonal neighbours, clockwise from up.
+pub const NEIGHBORS8: [(i64, i64); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+pub fn neighbors4((x, y): (i64, i64)) -> impl Iterator<Item = (i64, i64)> {
+    NEIGHBORS4.iter().map(move |&(dx, dy)| (x + dx, y + dy))
+}
+
+pub fn neighbors8((x, y): (i64, i64)) -> impl Iterator<Item = (i64, i64)> {
+    NEIGHBORS8.iter().map(move |&(dx, dy)| (x + dx, y + dy))
+}
+
+#[derive(Eq, PartialEq)]
+pub enum GridError {
+    Ragged {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    UnknownChar {
+        pos: (i64, i64),
+        c: char,
+    },
+}
+
+impl Display for GridError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            GridError::Ragged {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Row {} is {} cells wide, expected {}",
+                row, found, expected
+            ),
+            GridError::UnknownChar { pos, c } => {
+                write!(f, "Unexpected character {:?} at {:?}", c, pos)
+            }
+        }
+    }
+}
+
+impl Debug for GridError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        Display::fmt(self, f)
+    }
+}
+
+/// A rectangular grid stored row by row, with `(0, 0)` at the top left.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+
+impl<T: Clone> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Result<Self, GridError> {
+        let width = rows.first().map_or(0, Vec::len);
+        let height = rows.len();
+        let mut cells = Vec::with_capacity(width * height);
+
+        for (row, values) in rows.into_iter().enumerate() {
+            if values.len() != width {
+                return Err(GridError::Ragged {
+                    row,
+                    expected: width,
+                    found: values.len(),
+                });
+            }
+
+            cells.extend(values);
+        }
+
+        Ok(Grid {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    /// Reads one row per line, failing on characters `cell` doesn't map.
+    pub fn parse(text: &str, cell: impl Fn(char) -> Option<T>) -> Result<Self, GridError> {
+        let rows = text
+            .lines()
+            .enumerate()
+            .map(|(y, line)| {
+                line.chars()
+                    .enumerate()
+                    .map(|(x, c)| {
+                        cell(c).ok_or(GridError::UnknownChar {
+                            pos: (x as i64, y as i64),
+                            c,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Grid::from_rows(rows)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index(&self, (x, y): (i64, i64)) -> Option<usize> {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            Some(y as usize * self.width + x as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains(&self, pos: (i64, i64)) -> bool {
+        self.index(pos).is_some()
+    }
+
+    pub fn get(&self, pos: (i64, i64)) -> Option<&T> {
+        self.index(pos).map(|index| &self.cells[index])
+    }
+
+    pub fn get_mut(&mut self, pos: (i64, i64)) -> Option<&mut T> {
+        self.index(pos).map(move |index| &mut self.cells[index])
+    }
+
+    /// Same shape as `SparseGrid::bounds`, `None` when the grid is empty.
+    pub fn bounds(&self) -> Option<(i64, i64, i64, i64)> {
+        if self.cells.is_empty() {
+            None
+        } else {
+            Some((0, 0, self.width as i64 - 1, self.height as i64 - 1))
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ((i64, i64), &T)> {
+        let width = self.width;
+
+        self.cells
+            .iter()
+            .enumerate()
+            .map(move |(index, value)| (((index % width) as i64, (index / width) as i64), value))
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        self.cells.chunks(self.width.max(1))
+    }
+
+    pub fn row(&self, y: usize) -> Option<&[T]> {
+        self.rows().nth(y)
+    }
+
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        let height = if x < self.width { self.height } else { 0 };
+
+        self.cells
+            .iter()
+            .skip(x)
+            .step_by(self.width.max(1))
+            .take(height)
+    }
+
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        (0..self.width).map(move |x| self.column(x))
+    }
+
+    pub fn neighbors4(&self, pos: (i64, i64)) -> impl Iterator<Item = ((i64, i64), &T)> {
+        neighbors4(pos).filter_map(move |pos| self.get(pos).map(|value| (pos, value)))
+    }
+
+    pub fn neighbors8(&self, pos: (i64, i64)) -> impl Iterator<Item = ((i64, i64), &T)> {
+        neighbors8(pos).filter_map(move |pos| self.get(pos).map(|value| (pos, value)))
+    }
+
+    pub fn map<U>(&self, f: impl Fn(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+
+    /// One line per row, each ending in a newline.
+    pub fn render(&self, cell: impl Fn(&T) -> char) -> String {
+        let mut text = String::with_capacity((self.width + 1) * self.height);
+
+        for row in self.rows() {
+            text.extend(row.iter().map(&cell));
+            text.push('\n');
+        }
+
+        text
+    }
+}
+
+fn extend(
+    bounds: Option<(i64, i64, i64, i64)>,
+    (x, y): (i64, i64),
+) -> Option<(i64, i64, i64, i64)> {
+    Some(match bounds {
+        None => (x, y, x, y),
+        Some((min_x, min_y, max_x, max_y)) => {
+            (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+        }
+    })
+}
+
+/// A grid of unbounded extent, keeping track of the area set so far.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SparseGrid<T> {
+    cells: HashMap<(i64, i64), T>,
+    bounds: Option<(i64, i64, i64, i64)>,
+}
+
+impl<T> Default for SparseGrid<T> {
+    fn default() -> Self {
+        SparseGrid {
+            cells: HashMap::new(),
+            bounds: None,
+        }
+    }
+}
+
+impl<T> FromIterator<((i64, i64), T)> for SparseGrid<T> {
+    fn from_iter<I: IntoIterator<Item = ((i64, i64), T)>>(iter: I) -> Self {
+        let mut grid = SparseGrid::new();
+
+        for (pos, value) in iter {
+            grid.insert(pos, value);
+        }
+
+        grid
+    }
+}
+
+impl<T> SparseGrid<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads one row per line, leaving out characters `cell` doesn't map.
+    pub fn parse(text: &str, cell: impl Fn(char) -> Option<T>) -> Self {
+        let mut grid = SparseGrid::new();
+
+        for (y, line) in text.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if let Some(value) = cell(c) {
+                    grid.insert((x as i64, y as i64), value);
+                }
+            }
+        }
+
+        grid
+    }
+
+    pub fn get(&self, pos: (i64, i64)) -> Option<&T> {
+        self.cells.get(&pos)
+    }
+
+    pub fn get_mut(&mut self, pos: (i64, i64)) -> Option<&mut T> {
+        self.cells.get_mut(&pos)
+    }
+
+    pub fn insert(&mut self, pos: (i64, i64), value: T) -> Option<T> {
+        self.bounds = extend(self.bounds, pos);
+        self.cells.insert(pos, value)
+    }
+
+    pub fn remove(&mut self, (x, y): (i64, i64)) -> Option<T> {
+        let removed = self.cells.remove(&(x, y));
+
+        // Only a cell on the edge can shrink the bounds
+        if let Some((min_x, min_y, max_x, max_y)) = self.bounds {
+            if removed.is_some() && (x == min_x || x == max_x || y == min_y || y == max_y) {
+                self.bounds = self.cells.keys().cloned().fold(None, extend);
+            }
+        }
+
+        removed
+    }
+
+    pub fn contains(&self, pos: (i64, i64)) -> bool {
+        self.cells.contains_key(&pos)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = ((i64, i64), &T)> {
+        self.cells.iter().map(|(&pos, value)| (pos, value))
+    }
+
+    /// Smallest and largest coordinates set, as `(min_x, min_y, max_x, max_y)`.
+    pub fn bounds(&self) -> Option<(i64, i64, i64, i64)> {
+        self.bounds
+    }
+
+    /// Cells across the bounds at height `y`, `None` where nothing is set.
+    pub fn row(&self, y: i64) -> impl Iterator<Item = Option<&T>> {
+        let (min_x, max_x) = self.bounds.map_or((0, -1), |b| (b.0, b.2));
+        (min_x..=max_x).map(move |x| self.get((x, y)))
+    }
+
+    /// Cells across the bounds at `x`, `None` where nothing is set.
+    pub fn column(&self, x: i64) -> impl Iterator<Item = Option<&T>> {
+        let (min_y, max_y) = self.bounds.map_or((0, -1), |b| (b.1, b.3));
+        (min_y..=max_y).map(move |y| self.get((x, y)))
+    }
+
+    pub fn neighbors4(&self, pos: (i64, i64)) -> impl Iterator<Item = ((i64, i64), &T)> {
+        neighbors4(pos).filter_map(move |pos| self.get(pos).map(|value| (pos, value)))
+    }
+
+    pub fn neighbors8(&self, pos: (i64, i64)) -> impl Iterator<Item = ((i64, i64), &T)> {
+        neighbors8(pos).filter_map(move |pos| self.get(pos).map(|value| (pos, value)))
+    }
+
+    /// Draws the bounds top to bottom, with `empty` where nothing is set.
+    pub fn render(&self, empty: char, cell: impl Fn(&T) -> char) -> String {
+        let mut text = String::new();
+
+        if let Some((_, min_y, _, max_y)) = self.bounds {
+            for y in min_y..=max_y {
+                text.extend(self.row(y).map(|value| value.map_or(empty, &cell)));
+                text.push('\n');
+            }
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_grid() {
+        let grid = Grid::parse("#..\n.#.\n", |c| Some(c == '#')).unwrap();
+
+        assert_eq!((3, 2), (grid.width(), grid.height()));
+        assert_eq!(Some(&true), grid.get((1, 1)));
+        assert_eq!(None, grid.get((3, 0)));
+        assert_eq!(Some(&[false, true, false][..]), grid.row(1));
+        assert_eq!(
+            vec![true, false],
+            grid.column(0).cloned().collect::<Vec<_>>()
+        );
+        assert_eq!(3, grid.columns().count());
+        assert_eq!(
+            vec![(1, 0), (2, 1), (0, 1)],
+            grid.neighbors4((1, 1))
+                .map(|(pos, _)| pos)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(3, grid.neighbors8((0, 0)).count());
+        assert_eq!("#..\n.#.\n", grid.render(|&on| if on { '#' } else { '.' }));
+
+        assert_eq!(
+            Err(GridError::Ragged {
+                row: 1,
+                expected: 2,
+                found: 1
+            }),
+            Grid::parse("ab\nc", Some)
+        );
+        assert_eq!(
+            Err(GridError::UnknownChar {
+                pos: (1, 0),
+                c: '?'
+            }),
+            Grid::parse("#?", |c| if c == '#' { Some(()) } else { None })
+        );
+    }
+
+    #[test]
+    fn test_sparse_grid() {
+        let mut grid = SparseGrid::parse(" #\n# ", |c| if c == '#' { Some(c) } else { None });
+
+        assert_eq!(2, grid.len());
+        assert_eq!(Some((0, 0, 1, 1)), grid.bounds());
+
+        grid.insert((-1, 3), '#');
+        assert_eq!(Some((-1, 0, 1, 3)), grid.bounds());
+        assert_eq!("  #\n # \n   \n#  \n", grid.render(' ', |&c| c));
+
+        grid.remove((-1, 3));
+        assert_eq!(Some((0, 0, 1, 1)), grid.bounds());
+        assert_eq!(vec![None, Some(&'#')], grid.column(0).collect::<Vec<_>>());
+        assert_eq!(2, grid.neighbors8((0, 0)).count());
+    }
+}
@@ -0,0 +1,474 @@
+use crate::intcode::ascii::{Ascii, Output};
+use crate::intcode::computer::{RuntimeError, State, Status};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::{Debug, Display, Error, Formatter};
+use std::fs::read_to_string;
+use std::path::Path;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Room {
+    pub name: String,
+    pub description: Vec<String>,
+    pub doors: Vec<String>,
+    pub items: Vec<String>,
+}
+
+#[derive(Copy, Clone)]
+enum Section {
+    Doors,
+    Items,
+}
+
+fn room_name(line: &str) -> Option<&str> {
+    line.strip_prefix("== ")?.strip_suffix(" ==")
+}
+
+pub fn parse_rooms(lines: &[String]) -> Vec<Room> {
+    let mut rooms = Vec::new();
+    let mut lines = lines.iter().map(String::as_str).peekable();
+
+    while let Some(line) = lines.next() {
+        let name = match room_name(line) {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let mut room = Room {
+            name: name.to_string(),
+            description: Vec::new(),
+            doors: Vec::new(),
+            items: Vec::new(),
+        };
+        let mut section = None;
+
+        while let Some(&line) = lines.peek() {
+            if room_name(line).is_some() || line == "Command?" {
+                break;
+            }
+
+            lines.next();
+
+            match line {
+                "Doors here lead:" => section = Some(Section::Doors),
+                "Items here:" => section = Some(Section::Items),
+                "" => section = None,
+                line => match (section, line.strip_prefix("- ")) {
+                    (Some(Section::Doors), Some(door)) => room.doors.push(door.to_string()),
+                    (Some(Section::Items), Some(item)) => room.items.push(item.to_string()),
+                    _ => room.description.push(line.to_string()),
+                },
+            }
+        }
+
+        rooms.push(room);
+    }
+
+    rooms
+}
+
+#[derive(Eq, PartialEq)]
+pub enum AdventureError {
+    Runtime(RuntimeError),
+    Halted,
+    UnknownSave(String),
+    UnknownRoom(String),
+    Script(String),
+}
+
+impl Display for AdventureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            AdventureError::Runtime(error) => write!(f, "{}", error),
+            AdventureError::Halted => write!(f, "Program has halted"),
+            AdventureError::UnknownSave(name) => write!(f, "No save named {}", name),
+            AdventureError::UnknownRoom(name) => write!(f, "No known path to room {}", name),
+            AdventureError::Script(error) => write!(f, "Error reading script: {}", error),
+        }
+    }
+}
+
+impl Debug for AdventureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        Display::fmt(self, f)
+    }
+}
+
+impl From<RuntimeError> for AdventureError {
+    fn from(error: RuntimeError) -> Self {
+        AdventureError::Runtime(error)
+    }
+}
+
+struct Save {
+    state: State,
+    status: Status,
+    room: Option<String>,
+    inventory: Vec<String>,
+}
+
+pub struct Adventure {
+    ascii: Ascii,
+    status: Status,
+    room: Option<String>,
+    inventory: Vec<String>,
+    rooms: HashMap<String, Room>,
+    doors: HashMap<(String, String), String>,
+    /// Doors that were taken without arriving anywhere.
+    blocked: HashSet<(String, String)>,
+    ejected: HashSet<String>,
+    saves: HashMap<String, Save>,
+}
+
+impl Adventure {
+    /// Starts the program, returning the driver along with the opening output.
+    pub fn new(mut ascii: Ascii) -> Result<(Adventure, Output), AdventureError> {
+        let (status, output) = ascii.run()?;
+
+        let mut adventure = Adventure {
+            ascii,
+            status,
+            room: None,
+            inventory: Vec::new(),
+            rooms: HashMap::new(),
+            doors: HashMap::new(),
+            blocked: HashSet::new(),
+            ejected: HashSet::new(),
+            saves: HashMap::new(),
+        };
+
+        adventure.observe(None, &output);
+
+        Ok((adventure, output))
+    }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn room(&self) -> Option<&Room> {
+        self.room.as_ref().and_then(|name| self.rooms.get(name))
+    }
+
+    pub fn rooms(&self) -> impl Iterator<Item = &Room> {
+        self.rooms.values()
+    }
+
+    pub fn inventory(&self) -> &[String] {
+        &self.inventory
+    }
+
+    /// The room reached by taking `door` from `room`, if it has been visited.
+    pub fn destination(&self, room: &str, door: &str) -> Option<&str> {
+        self.doors
+            .get(&(room.to_string(), door.to_string()))
+            .map(String::as_str)
+    }
+
+    fn observe(&mut self, door: Option<(String, String)>, output: &Output) {
+        let rooms = parse_rooms(&output.lines);
+
+        match (door, rooms.first()) {
+            (Some(door), Some(first)) => {
+                self.doors.insert(door, first.name.clone());
+
+                // Walking in and immediately being sent elsewhere
+                if rooms.len() > 1 {
+                    self.ejected.insert(first.name.clone());
+                }
+            }
+            // "You can't go that way", so don't try it again
+            (Some(door), None) => {
+                self.blocked.insert(door);
+            }
+            _ => {}
+        }
+
+        if let Some(last) = rooms.last() {
+            self.room = Some(last.name.clone());
+        }
+
+        for room in rooms {
+            self.rooms.insert(room.name.clone(), room);
+        }
+
+        for line in &output.lines {
+            if let Some(item) = line.strip_prefix("You take the ") {
+                self.inventory.push(item.trim_end_matches('.').to_string());
+            } else if let Some(item) = line.strip_prefix("You drop the ") {
+                let item = item.trim_end_matches('.');
+                self.inventory.retain(|held| held != item);
+            }
+        }
+    }
+
+    pub fn command(&mut self, command: &str) -> Result<Output, AdventureError> {
+        if self.status == Status::Halted {
+            return Err(AdventureError::Halted);
+        }
+
+        let door = self
+            .room()
+            .filter(|room| room.doors.iter().any(|door| door == command))
+            .map(|room| (room.name.clone(), command.to_string()));
+
+        self.ascii.send_line(command);
+
+        let (status, output) = self.ascii.run()?;
+        self.status = status;
+        self.observe(door, &output);
+
+        Ok(output)
+    }
+
+    pub fn save(&mut self, name: &str) {
+        let save = Save {
+            state: self.ascii.computer().save_state(),
+            status: self.status,
+            room: self.room.clone(),
+            inventory: self.inventory.clone(),
+        };
+
+        self.saves.insert(name.to_string(), save);
+    }
+
+    /// Returns the machine to a saved state. The map of the world is kept.
+    pub fn restore(&mut self, name: &str) -> Result<(), AdventureError> {
+        let save = self
+            .saves
+            .get(name)
+            .ok_or_else(|| AdventureError::UnknownSave(name.to_string()))?;
+
+        self.ascii.computer_mut().load_state(save.state.clone());
+        self.status = save.status;
+        self.room = save.room.clone();
+        self.inventory = save.inventory.clone();
+
+        Ok(())
+    }
+
+    /// Shortest sequence of doors between two known rooms, avoiding rooms
+    /// that eject the droid unless they are the destination.
+    pub fn path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        self.search(from, |room, _| room == to)
+    }
+
+    fn search(&self, from: &str, found: impl Fn(&str, &Room) -> bool) -> Option<Vec<String>> {
+        let mut queue = VecDeque::new();
+        let mut visited = HashSet::new();
+
+        queue.push_back((from, Vec::new()));
+        visited.insert(from);
+
+        while let Some((name, path)) = queue.pop_front() {
+            let room = self.rooms.get(name)?;
+
+            if found(name, room) {
+                return Some(path);
+            }
+
+            if self.ejected.contains(name) && name != from {
+                continue;
+            }
+
+            for door in &room.doors {
+                if let Some(next) = self.destination(name, door) {
+                    if visited.insert(next) {
+                        let mut path = path.clone();
+                        path.push(door.clone());
+                        queue.push_back((next, path));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn untried(&self, room: &str, door: &str) -> bool {
+        self.destination(room, door).is_none()
+            && !self.blocked.contains(&(room.to_string(), door.to_string()))
+    }
+
+    /// Path from the current room through the nearest door not yet taken.
+    fn frontier(&self) -> Option<Vec<String>> {
+        let current = self.room.as_ref()?;
+
+        let mut path = self.search(current, |name, room| {
+            !self.ejected.contains(name) && room.doors.iter().any(|door| self.untried(name, door))
+        })?;
+
+        let room = self.rooms.get(self.follow(current, &path))?;
+        let door = room
+            .doors
+            .iter()
+            .find(|door| self.untried(&room.name, door))?;
+
+        path.push(door.clone());
+        Some(path)
+    }
+
+    fn follow<'a>(&'a self, mut room: &'a str, path: &[String]) -> &'a str {
+        for door in path {
+            room = self.destination(room, door).unwrap_or(room);
+        }
+
+        room
+    }
+
+    pub fn walk(&mut self, path: &[String]) -> Result<Output, AdventureError> {
+        let mut output = Output::default();
+
+        for door in path {
+            output.extend(self.command(door)?);
+        }
+
+        Ok(output)
+    }
+
+    pub fn walk_to(&mut self, room: &str) -> Result<Output, AdventureError> {
+        let path = self
+            .room
+            .as_ref()
+            .and_then(|current| self.path(current, room))
+            .ok_or_else(|| AdventureError::UnknownRoom(room.to_string()))?;
+
+        self.walk(&path)
+    }
+
+    /// Takes every door until the whole reachable world is mapped. Doors that
+    /// lead nowhere are only tried once.
+    pub fn explore(&mut self) -> Result<Output, AdventureError> {
+        let mut output = Output::default();
+
+        while let Some(path) = self.frontier() {
+            output.extend(self.walk(&path)?);
+        }
+
+        Ok(output)
+    }
+
+    /// Runs a script of commands, one per line. Blank lines and lines starting
+    /// with `#` are skipped, and `!save <name>`, `!restore <name>`,
+    /// `!goto <room>` and `!explore` drive the adventure itself.
+    pub fn run_script(&mut self, script: &str) -> Result<Output, AdventureError> {
+        let mut output = Output::default();
+
+        for line in script.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix("!save ") {
+                self.save(name.trim());
+            } else if let Some(name) = line.strip_prefix("!restore ") {
+                self.restore(name.trim())?;
+            } else if let Some(room) = line.strip_prefix("!goto ") {
+                output.extend(self.walk_to(room.trim())?);
+            } else if line == "!explore" {
+                output.extend(self.explore()?);
+            } else {
+                output.extend(self.command(line)?);
+            }
+        }
+
+        Ok(output)
+    }
+
+    pub fn run_script_file(&mut self, path: impl AsRef<Path>) -> Result<Output, AdventureError> {
+        let script =
+            read_to_string(path).map_err(|error| AdventureError::Script(error.to_string()))?;
+
+        self.run_script(&script)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::ascii::ECHO;
+
+    const HULL_BREACH: &str = "
+
+== Hull Breach ==
+You got in through a hole in the floor here.
+
+Doors here lead:
+- north
+- west
+
+Items here:
+- mutex
+
+Command?";
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    // A program that prints `intro`, then answers every line of input with `reply`
+    fn scripted(intro: &str, reply: &str) -> String {
+        let print = |text: &str| {
+            text.bytes()
+                .map(|c| format!("104,{},", c))
+                .collect::<String>()
+        };
+
+        let mut program = print(intro);
+        let read = program.matches(',').count();
+
+        // Read into 1000 until a newline, then reply and go back to reading
+        program += &format!("3,1000,1008,1000,10,1001,1006,1001,{},", read);
+        program += &print(reply);
+        program += &format!("1105,1,{}", read);
+
+        program
+    }
+
+    #[test]
+    fn test_parse_rooms() {
+        let rooms = parse_rooms(&lines(HULL_BREACH));
+
+        assert_eq!(
+            rooms,
+            vec![Room {
+                name: "Hull Breach".to_string(),
+                description: vec!["You got in through a hole in the floor here.".to_string()],
+                doors: vec!["north".to_string(), "west".to_string()],
+                items: vec!["mutex".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_save_restore() {
+        let (mut adventure, _) = Adventure::new(Ascii::load(ECHO).unwrap()).unwrap();
+
+        adventure.save("start");
+
+        assert_eq!("first", adventure.command("first").unwrap().text());
+        assert_eq!(Status::Halted, adventure.status());
+        assert_eq!(Err(AdventureError::Halted), adventure.command("again"));
+
+        adventure.restore("start").unwrap();
+
+        assert_eq!("second", adventure.command("second").unwrap().text());
+        assert_eq!(
+            Err(AdventureError::UnknownSave("missing".to_string())),
+            adventure.restore("missing")
+        );
+    }
+
+    #[test]
+    fn test_explore_blocked() {
+        let intro = "== Hull Breach ==\n\nDoors here lead:\n- north\n\nCommand?\n";
+        let program = scripted(intro, "You can't go that way.\n\nCommand?\n");
+        let (mut adventure, _) = Adventure::new(Ascii::load(&program).unwrap()).unwrap();
+
+        let output = adventure.explore().unwrap();
+
+        assert_eq!(vec!["You can't go that way.", "", "Command?"], output.lines);
+        assert_eq!("Hull Breach", adventure.room().unwrap().name);
+        assert_eq!(None, adventure.destination("Hull Breach", "north"));
+        assert_eq!(None, adventure.frontier());
+    }
+}
@@ -0,0 +1,201 @@
+use crate::intcode::computer::{Computer, RuntimeError, Status};
+use crate::intcode::parser::ParseError;
+use std::io::{stdin, stdout, Write};
+use std::iter::once;
+use std::mem::take;
+
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Output {
+    pub lines: Vec<String>,
+    pub answers: Vec<i64>,
+}
+
+impl Output {
+    pub fn answer(&self) -> Option<i64> {
+        self.answers.last().cloned()
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn extend(&mut self, other: Output) {
+        self.lines.extend(other.lines);
+        self.answers.extend(other.answers);
+    }
+}
+
+fn is_ascii(value: i64) -> bool {
+    (0..=127).contains(&value)
+}
+
+pub fn encode(line: &str) -> impl Iterator<Item = i64> + '_ {
+    line.bytes().map(i64::from).chain(once(b'\n' as i64))
+}
+
+pub struct Ascii {
+    computer: Computer,
+    partial: String,
+}
+
+impl Ascii {
+    pub fn new(computer: Computer) -> Ascii {
+        Ascii {
+            computer,
+            partial: String::new(),
+        }
+    }
+
+    pub fn load(program: &str) -> Result<Ascii, ParseError<'_>> {
+        Computer::load(program).map(Ascii::new)
+    }
+
+    pub fn computer(&self) -> &Computer {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Computer {
+        &mut self.computer
+    }
+
+    pub fn into_inner(self) -> Computer {
+        self.computer
+    }
+
+    pub fn send_line(&mut self, line: &str) {
+        for code in encode(line) {
+            self.computer.push_input(code);
+        }
+    }
+
+    fn decode(&mut self, value: i64, output: &mut Output) {
+        if !is_ascii(value) {
+            output.answers.push(value);
+        } else if value == b'\n' as i64 {
+            output.lines.push(take(&mut self.partial));
+        } else {
+            self.partial.push(value as u8 as char);
+        }
+    }
+
+    /// Runs until the program halts or waits for input. A line left without a
+    /// trailing newline (usually a prompt) is flushed, since nothing more can
+    /// be printed until the program is given input.
+    pub fn run(&mut self) -> Result<(Status, Output), RuntimeError> {
+        let status = self.computer.resume()?;
+        let mut output = Output::default();
+
+        for value in self.computer.take_output() {
+            self.decode(value, &mut output);
+        }
+
+        if !self.partial.is_empty() {
+            output.lines.push(take(&mut self.partial));
+        }
+
+        Ok((status, output))
+    }
+
+    /// Feeds each line in turn, collecting output until the program halts or
+    /// asks for more input than was given.
+    pub fn execute<'a>(
+        &mut self,
+        lines: impl IntoIterator<Item = &'a str>,
+    ) -> Result<(Status, Output), RuntimeError> {
+        let (mut status, mut output) = self.run()?;
+
+        for line in lines {
+            if status == Status::Halted {
+                break;
+            }
+
+            self.send_line(line);
+
+            let (next, more) = self.run()?;
+            status = next;
+            output.extend(more);
+        }
+
+        Ok((status, output))
+    }
+
+    /// Runs the program on the terminal, reading lines of text from stdin as
+    /// input. Returns the non-ASCII values output along the way.
+    pub fn interactive(&mut self) -> Result<Vec<i64>, RuntimeError> {
+        let mut answers = Vec::new();
+        let mut stdout = stdout();
+
+        loop {
+            let status = self.computer.resume()?;
+
+            for value in self.computer.take_output() {
+                if is_ascii(value) {
+                    write!(stdout, "{}", value as u8 as char)
+                } else {
+                    answers.push(value);
+                    writeln!(stdout, "{}", value)
+                }
+                .map_err(|_| RuntimeError::OutputError)?;
+            }
+
+            stdout.flush().map_err(|_| RuntimeError::OutputError)?;
+
+            match status {
+                Status::Halted => return Ok(answers),
+                Status::AwaitingInput => {
+                    let mut line = String::new();
+                    let read = stdin()
+                        .read_line(&mut line)
+                        .map_err(|_| RuntimeError::InputError)?;
+
+                    if read == 0 {
+                        return Err(RuntimeError::InputError);
+                    }
+
+                    self.send_line(line.trim_end_matches(&['\r', '\n'][..]));
+                }
+            }
+        }
+    }
+}
+
+/// Echoes a line of input, then outputs 1000.
+#[cfg(test)]
+pub(crate) const ECHO: &str = "3,100,4,100,1008,100,10,101,1006,101,0,104,1000,99";
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(vec![72, 105, 10], encode("Hi").collect::<Vec<_>>());
+        assert_eq!(vec![10], encode("").collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_run() {
+        let mut ascii = Ascii::load(ECHO).unwrap();
+
+        let (status, output) = ascii.run().unwrap();
+        assert_eq!(Status::AwaitingInput, status);
+        assert_eq!(Output::default(), output);
+
+        ascii.send_line("hello");
+
+        let (status, output) = ascii.run().unwrap();
+        assert_eq!(Status::Halted, status);
+        assert_eq!(vec!["hello"], output.lines);
+        assert_eq!(Some(1000), output.answer());
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut ascii = Ascii::load(ECHO).unwrap();
+
+        let (status, output) = ascii.execute(vec!["abc", "ignored"]).unwrap();
+        assert_eq!(Status::Halted, status);
+        assert_eq!("abc", output.text());
+        assert_eq!(vec![1000], output.answers);
+    }
+}
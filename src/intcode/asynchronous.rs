@@ -0,0 +1,273 @@
+use crate::intcode::computer::{Computer, RuntimeError, Status};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+struct Channel {
+    queue: VecDeque<i64>,
+    waker: Option<Waker>,
+    senders: usize,
+    receiving: bool,
+}
+
+/// Sending half of a single-threaded, unbounded channel.
+pub struct AsyncSender {
+    channel: Rc<RefCell<Channel>>,
+}
+
+pub struct AsyncReceiver {
+    channel: Rc<RefCell<Channel>>,
+}
+
+pub fn channel() -> (AsyncSender, AsyncReceiver) {
+    let channel = Rc::new(RefCell::new(Channel {
+        queue: VecDeque::new(),
+        waker: None,
+        senders: 1,
+        receiving: true,
+    }));
+
+    let sender = AsyncSender {
+        channel: channel.clone(),
+    };
+
+    (sender, AsyncReceiver { channel })
+}
+
+impl AsyncSender {
+    /// Fails, handing the value back, if the receiver has been dropped.
+    pub fn send(&self, value: i64) -> Result<(), i64> {
+        let mut channel = self.channel.borrow_mut();
+
+        if !channel.receiving {
+            return Err(value);
+        }
+
+        channel.queue.push_back(value);
+
+        if let Some(waker) = channel.waker.take() {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+}
+
+impl Clone for AsyncSender {
+    fn clone(&self) -> Self {
+        self.channel.borrow_mut().senders += 1;
+
+        AsyncSender {
+            channel: self.channel.clone(),
+        }
+    }
+}
+
+impl Drop for AsyncSender {
+    fn drop(&mut self) {
+        let mut channel = self.channel.borrow_mut();
+        channel.senders -= 1;
+
+        if channel.senders == 0 {
+            if let Some(waker) = channel.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+pub struct Recv<'a> {
+    channel: &'a RefCell<Channel>,
+}
+
+impl Future for Recv<'_> {
+    type Output = Option<i64>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<i64>> {
+        let mut channel = self.channel.borrow_mut();
+
+        if let Some(value) = channel.queue.pop_front() {
+            Poll::Ready(Some(value))
+        } else if channel.senders == 0 {
+            Poll::Ready(None)
+        } else {
+            channel.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl AsyncReceiver {
+    /// Waits for the next value, or `None` once every sender is gone.
+    pub fn recv(&self) -> Recv<'_> {
+        Recv {
+            channel: &self.channel,
+        }
+    }
+
+    pub fn try_recv(&self) -> Option<i64> {
+        self.channel.borrow_mut().queue.pop_front()
+    }
+}
+
+impl Drop for AsyncReceiver {
+    fn drop(&mut self) {
+        self.channel.borrow_mut().receiving = false;
+    }
+}
+
+impl Computer {
+    /// Runs the program, awaiting `input` whenever it needs a value that has
+    /// not been pushed already. The computer must not have channels set.
+    pub async fn run_async(
+        &mut self,
+        input: &AsyncReceiver,
+        output: &AsyncSender,
+    ) -> Result<(), RuntimeError> {
+        loop {
+            let status = self.resume()?;
+
+            for value in self.take_output() {
+                output.send(value).map_err(|_| RuntimeError::OutputError)?;
+            }
+
+            match status {
+                Status::Halted => return Ok(()),
+                Status::AwaitingInput => {
+                    let value = input.recv().await.ok_or(RuntimeError::InputError)?;
+                    self.push_input(value);
+                }
+            }
+        }
+    }
+}
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+struct TaskWaker {
+    id: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready.lock().unwrap().push_back(self.id);
+    }
+}
+
+pub struct JoinHandle<T> {
+    result: Rc<RefCell<Option<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// The task's result, once the executor has run it to completion.
+    pub fn take(&self) -> Option<T> {
+        self.result.borrow_mut().take()
+    }
+}
+
+/// Polls spawned tasks on the current thread until none can make progress.
+#[derive(Default)]
+pub struct Executor {
+    tasks: Vec<Option<Task>>,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn<T: 'static>(
+        &mut self,
+        future: impl Future<Output = T> + 'static,
+    ) -> JoinHandle<T> {
+        let result = Rc::new(RefCell::new(None));
+        let handle = JoinHandle {
+            result: result.clone(),
+        };
+
+        let task = async move {
+            let value = future.await;
+            *result.borrow_mut() = Some(value);
+        };
+
+        self.ready.lock().unwrap().push_back(self.tasks.len());
+        self.tasks.push(Some(Box::pin(task)));
+
+        handle
+    }
+
+    /// Runs until every task has finished or is waiting on something that
+    /// will never arrive. Returns how many tasks are left unfinished.
+    pub fn run(&mut self) -> usize {
+        loop {
+            let next = self.ready.lock().unwrap().pop_front();
+            let id = match next {
+                Some(id) => id,
+                None => break,
+            };
+
+            if let Some(task) = &mut self.tasks[id] {
+                let waker = Waker::from(Arc::new(TaskWaker {
+                    id,
+                    ready: self.ready.clone(),
+                }));
+
+                if task
+                    .as_mut()
+                    .poll(&mut Context::from_waker(&waker))
+                    .is_ready()
+                {
+                    self.tasks[id] = None;
+                }
+            }
+        }
+
+        self.tasks.iter().filter(|task| task.is_some()).count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Outputs its input plus one
+    const INCREMENT: &str = "3,9,1001,9,1,9,4,9,99";
+
+    #[test]
+    fn test_chain() {
+        let mut executor = Executor::new();
+        let (first, mut input) = channel();
+
+        for _ in 0..500 {
+            let (sender, receiver) = channel();
+            let mut computer = Computer::load(INCREMENT).unwrap();
+
+            executor.spawn(async move { computer.run_async(&input, &sender).await });
+            input = receiver;
+        }
+
+        first.send(0).unwrap();
+
+        assert_eq!(0, executor.run());
+        assert_eq!(Some(500), input.try_recv());
+    }
+
+    #[test]
+    fn test_stalled() {
+        let mut executor = Executor::new();
+        let (_sender, input) = channel();
+        let (output, _receiver) = channel();
+        let mut computer = Computer::load(INCREMENT).unwrap();
+
+        let handle = executor.spawn(async move { computer.run_async(&input, &output).await });
+
+        assert_eq!(1, executor.run());
+        assert_eq!(None, handle.take());
+    }
+}
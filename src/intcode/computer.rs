@@ -1,6 +1,8 @@
+use crate::intcode::ascii::encode;
 use crate::intcode::parser::{read_program, ParseError};
 use crossterm::style::Print;
 use crossterm::ExecutableCommand;
+use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::fmt::{Debug, Display, Error, Formatter};
 use std::io::{stderr, stdin};
@@ -12,6 +14,27 @@ pub struct Computer {
     rb: usize,
     input: Option<Receiver<i64>>,
     output: Option<Sender<i64>>,
+    queue: VecDeque<i64>,
+    buffer: Vec<i64>,
+    // Print output as it happens rather than buffering it, while `run` is going
+    stream: bool,
+    instructions: u64,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct State {
+    pub memory: Vec<i64>,
+    pub pc: usize,
+    pub rb: usize,
+    pub queue: Vec<i64>,
+    pub output: Vec<i64>,
+    pub instructions: u64,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Status {
+    Halted,
+    AwaitingInput,
 }
 
 #[derive(Eq, PartialEq)]
@@ -52,17 +75,30 @@ impl Debug for RuntimeError {
 impl Computer {
     pub fn load(program: &str) -> Result<Computer, ParseError> {
         let memory = read_program(program)?;
-        let pc = 0;
-        let rb = 0;
-        let computer = Computer {
+        let state = State {
             memory,
-            pc,
-            rb,
-            input: None,
-            output: None,
+            pc: 0,
+            rb: 0,
+            queue: Vec::new(),
+            output: Vec::new(),
+            instructions: 0,
         };
 
-        Ok(computer)
+        Ok(Computer::from_state(state))
+    }
+
+    pub fn from_state(state: State) -> Computer {
+        Computer {
+            memory: state.memory,
+            pc: state.pc,
+            rb: state.rb,
+            input: None,
+            output: None,
+            queue: state.queue.into_iter().collect(),
+            buffer: state.output,
+            stream: false,
+            instructions: state.instructions,
+        }
     }
 
     pub fn set_input(&mut self, receiver: Receiver<i64>) {
@@ -81,6 +117,48 @@ impl Computer {
         self.input.take()
     }
 
+    /// Queues a value to be consumed by the next input instruction, ahead of
+    /// anything waiting on the input channel.
+    pub fn push_input(&mut self, value: i64) {
+        self.queue.push_back(value);
+    }
+
+    /// Takes all values output since the last call, when no output channel is set.
+    pub fn take_output(&mut self) -> Vec<i64> {
+        std::mem::take(&mut self.buffer)
+    }
+
+    pub fn memory(&self) -> &[i64] {
+        &self.memory
+    }
+
+    /// Number of instructions executed so far.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Captures memory, registers, queued input and output not yet taken.
+    /// Channels are not part of the state.
+    pub fn save_state(&self) -> State {
+        State {
+            memory: self.memory.clone(),
+            pc: self.pc,
+            rb: self.rb,
+            queue: self.queue.iter().cloned().collect(),
+            output: self.buffer.clone(),
+            instructions: self.instructions,
+        }
+    }
+
+    pub fn load_state(&mut self, state: State) {
+        self.memory = state.memory;
+        self.pc = state.pc;
+        self.rb = state.rb;
+        self.queue = state.queue.into_iter().collect();
+        self.buffer = state.output;
+        self.instructions = state.instructions;
+    }
+
     fn to_address(&self, address: i64) -> Result<usize, RuntimeError> {
         usize::try_from(address).map_err(|_| RuntimeError::OutOfBounds(address))
     }
@@ -169,8 +247,25 @@ impl Computer {
         }
     }
 
-    pub fn run(&mut self) -> Result<(), RuntimeError> {
+    fn next_input(&mut self) -> Result<Option<i64>, RuntimeError> {
+        if let Some(value) = self.queue.pop_front() {
+            Ok(Some(value))
+        } else if let Some(receiver) = &self.input {
+            receiver
+                .recv()
+                .map(Some)
+                .map_err(|_| RuntimeError::InputError)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Runs until the program halts, or until it needs input and there is
+    /// neither a queued value nor an input channel to wait on.
+    pub fn resume(&mut self) -> Result<Status, RuntimeError> {
         loop {
+            let pc = self.pc;
+
             match self.opcode()? {
                 // Add
                 [1, a, b, r] => {
@@ -187,31 +282,23 @@ impl Computer {
                     self.write(r, first * second)?;
                 }
                 // Input
-                [3, r, 0, 0] => {
-                    let input = if let Some(receiver) = &self.input {
-                        receiver.recv().map_err(|_| RuntimeError::InputError)?
-                    } else {
-                        stderr()
-                            .execute(Print("> "))
-                            .map_err(|_| RuntimeError::InputError)?;
-
-                        let mut input = String::new();
-                        stdin()
-                            .read_line(&mut input)
-                            .map_err(|_| RuntimeError::InputError)?;
-
-                        input.trim().parse().map_err(|_| RuntimeError::InputError)?
-                    };
-
-                    self.write(r, input)?;
-                }
+                [3, r, 0, 0] => match self.next_input()? {
+                    Some(input) => self.write(r, input)?,
+                    None => {
+                        // Rewind so the instruction is retried once input is pushed
+                        self.pc = pc;
+                        return Ok(Status::AwaitingInput);
+                    }
+                },
                 // Output
                 [4, a, 0, 0] => {
                     let value = self.read(a)?;
                     if let Some(sender) = &self.output {
                         sender.send(value).map_err(|_| RuntimeError::OutputError)?;
-                    } else {
+                    } else if self.stream {
                         println!("{}", value);
+                    } else {
+                        self.buffer.push(value);
                     }
                 }
                 // Jump-if-true
@@ -252,11 +339,60 @@ impl Computer {
                     self.rb = self.to_address(self.rb as i64 + offset)?;
                 }
                 // Exit
-                [99, 0, 0, 0] => return Ok(()),
+                [99, 0, 0, 0] => {
+                    self.pc = pc;
+                    return Ok(Status::Halted);
+                }
                 opcode => {
                     return Err(RuntimeError::UnrecognizedOpcode(opcode));
                 }
             }
+
+            self.instructions += 1;
+        }
+    }
+
+    /// Runs the program on the terminal, printing output as it is produced.
+    /// Whenever the program waits for input it asks for a line, which is
+    /// sent as a number if it is one and as ASCII text otherwise.
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        self.stream = true;
+        let result = self.run_terminal();
+        self.stream = false;
+
+        result
+    }
+
+    fn run_terminal(&mut self) -> Result<(), RuntimeError> {
+        loop {
+            match self.resume()? {
+                Status::Halted => return Ok(()),
+                Status::AwaitingInput => {
+                    stderr()
+                        .execute(Print("Input (a number or a line of text): "))
+                        .map_err(|_| RuntimeError::InputError)?;
+
+                    let mut input = String::new();
+                    let read = stdin()
+                        .read_line(&mut input)
+                        .map_err(|_| RuntimeError::InputError)?;
+
+                    if read == 0 {
+                        return Err(RuntimeError::InputError);
+                    }
+
+                    let line = input.trim_end_matches(&['\r', '\n'][..]);
+
+                    match line.trim().parse() {
+                        Ok(value) => self.push_input(value),
+                        Err(_) => {
+                            for code in encode(line) {
+                                self.push_input(code);
+                            }
+                        }
+                    }
+                }
+            }
         }
     }
 }
@@ -0,0 +1,493 @@
+use crate::geometry::{Dir4, Point2};
+use crate::grid::SparseGrid;
+use crate::intcode::computer::{Computer, RuntimeError, Status};
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{Debug, Display, Error, Formatter};
+
+fn neighbour(pos: (i64, i64), dir: Dir4) -> (i64, i64) {
+    Point2::from(pos).step(dir).into()
+}
+
+/// How a robot is told to move and how it reports what it found.
+pub trait MoveProtocol {
+    type Tile: Clone;
+
+    fn command(&self, dir: Dir4) -> i64;
+
+    /// Decodes the robot's reply, or `None` if it is not a known status.
+    fn tile(&self, status: i64) -> Option<Self::Tile>;
+
+    fn passable(&self, tile: &Self::Tile) -> bool;
+
+    /// What the robot is standing on before it has moved.
+    fn start(&self) -> Self::Tile;
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Strategy {
+    /// Explore depth first, walking back along the way each dead end was
+    /// reached.
+    DepthFirst,
+    /// Walk the shortest known route to the closest unexplored tile.
+    NearestFrontier,
+}
+
+#[derive(Eq, PartialEq)]
+pub enum ExploreError {
+    Runtime(RuntimeError),
+    Halted,
+    UnexpectedOutput(Vec<i64>),
+    Blocked((i64, i64)),
+}
+
+impl Display for ExploreError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            ExploreError::Runtime(error) => write!(f, "{}", error),
+            ExploreError::Halted => write!(f, "Robot program has halted"),
+            ExploreError::UnexpectedOutput(output) => {
+                write!(f, "Expected a single known status, got {:?}", output)
+            }
+            ExploreError::Blocked((x, y)) => {
+                write!(f, "Could not move through explored tile ({}, {})", x, y)
+            }
+        }
+    }
+}
+
+impl Debug for ExploreError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        Display::fmt(self, f)
+    }
+}
+
+impl From<RuntimeError> for ExploreError {
+    fn from(error: RuntimeError) -> Self {
+        ExploreError::Runtime(error)
+    }
+}
+
+/// Called after every move with the map so far and the robot's position.
+pub type Observer<T> = Box<dyn FnMut(&SparseGrid<T>, (i64, i64))>;
+
+pub struct Explorer<P: MoveProtocol> {
+    computer: Computer,
+    protocol: P,
+    position: (i64, i64),
+    map: SparseGrid<P::Tile>,
+    moves: usize,
+    observer: Option<Observer<P::Tile>>,
+}
+
+impl<P: MoveProtocol> Explorer<P> {
+    pub fn new(computer: Computer, protocol: P) -> Self {
+        let mut map = SparseGrid::new();
+        map.insert((0, 0), protocol.start());
+
+        Explorer {
+            computer,
+            protocol,
+            position: (0, 0),
+            map,
+            moves: 0,
+            observer: None,
+        }
+    }
+
+    pub fn observe(&mut self, observer: impl FnMut(&SparseGrid<P::Tile>, (i64, i64)) + 'static) {
+        self.observer = Some(Box::new(observer));
+    }
+
+    pub fn position(&self) -> (i64, i64) {
+        self.position
+    }
+
+    pub fn map(&self) -> &SparseGrid<P::Tile> {
+        &self.map
+    }
+
+    pub fn into_map(self) -> SparseGrid<P::Tile> {
+        self.map
+    }
+
+    /// Number of move commands sent to the robot so far.
+    pub fn moves(&self) -> usize {
+        self.moves
+    }
+
+    fn passable(&self, pos: (i64, i64)) -> bool {
+        self.map
+            .get(pos)
+            .filter(|tile| self.protocol.passable(tile))
+            .is_some()
+    }
+
+    /// Sends a single move, recording and returning what the robot found.
+    pub fn step(&mut self, dir: Dir4) -> Result<P::Tile, ExploreError> {
+        self.computer.push_input(self.protocol.command(dir));
+
+        if self.computer.resume()? == Status::Halted {
+            return Err(ExploreError::Halted);
+        }
+
+        self.moves += 1;
+
+        let output = self.computer.take_output();
+        let tile = match output[..] {
+            [status] => self.protocol.tile(status),
+            _ => None,
+        }
+        .ok_or(ExploreError::UnexpectedOutput(output))?;
+
+        let next = neighbour(self.position, dir);
+        if self.protocol.passable(&tile) {
+            self.position = next;
+        }
+
+        self.map.insert(next, tile.clone());
+
+        if let Some(observer) = &mut self.observer {
+            observer(&self.map, self.position);
+        }
+
+        Ok(tile)
+    }
+
+    fn walk(&mut self, path: &[Dir4]) -> Result<(), ExploreError> {
+        for &dir in path {
+            let next = neighbour(self.position, dir);
+            let tile = self.step(dir)?;
+
+            if !self.protocol.passable(&tile) {
+                return Err(ExploreError::Blocked(next));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn search(
+        &self,
+        from: (i64, i64),
+        found: impl Fn((i64, i64)) -> bool,
+    ) -> Option<((i64, i64), Vec<Dir4>)> {
+        let mut queue = VecDeque::new();
+        let mut visited = HashSet::new();
+
+        queue.push_back((from, Vec::new()));
+        visited.insert(from);
+
+        while let Some((pos, path)) = queue.pop_front() {
+            if found(pos) {
+                return Some((pos, path));
+            }
+
+            for &dir in &Dir4::ALL {
+                let next = neighbour(pos, dir);
+
+                if self.passable(next) && visited.insert(next) {
+                    let mut path = path.clone();
+                    path.push(dir);
+                    queue.push_back((next, path));
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Shortest route over explored, passable tiles.
+    pub fn path(&self, from: (i64, i64), to: (i64, i64)) -> Option<Vec<Dir4>> {
+        self.search(from, |pos| pos == to).map(|(_, path)| path)
+    }
+
+    pub fn explore(&mut self, strategy: Strategy) -> Result<(), ExploreError> {
+        match strategy {
+            Strategy::DepthFirst => self.explore_depth_first(),
+            Strategy::NearestFrontier => self.explore_nearest_frontier(),
+        }
+    }
+
+    fn explore_depth_first(&mut self) -> Result<(), ExploreError> {
+        // Directions left to try at each tile, and how that tile was entered
+        let mut stack = vec![(Dir4::ALL.iter(), None)];
+
+        while let Some((dirs, entered)) = stack.last_mut() {
+            match dirs.next() {
+                Some(&dir) => {
+                    if self.map.contains(neighbour(self.position, dir)) {
+                        continue;
+                    }
+
+                    let tile = self.step(dir)?;
+
+                    if self.protocol.passable(&tile) {
+                        stack.push((Dir4::ALL.iter(), Some(dir)));
+                    }
+                }
+                None => {
+                    if let Some(dir) = entered.take() {
+                        self.walk(&[dir.invert()])?;
+                    }
+
+                    stack.pop();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn explore_nearest_frontier(&mut self) -> Result<(), ExploreError> {
+        let unexplored = |map: &SparseGrid<P::Tile>, pos| {
+            Dir4::ALL
+                .iter()
+                .find(|&&dir| !map.contains(neighbour(pos, dir)))
+                .cloned()
+        };
+
+        while let Some((pos, path)) =
+            self.search(self.position, |pos| unexplored(&self.map, pos).is_some())
+        {
+            self.walk(&path)?;
+
+            let dir = unexplored(&self.map, pos).unwrap();
+            self.step(dir)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds a robot for a maze drawn with `#` walls, `.` open tiles, `O` for the
+/// goal and `D` where it starts. It takes move commands from 1 in `order` and
+/// replies 0, 1 or 2 like the repair droid, or 7 for a `?` tile.
+#[cfg(test)]
+pub(crate) fn maze_program(maze: &str, order: [Dir4; 4]) -> String {
+    let lines = maze.lines().collect::<Vec<_>>();
+    let width = lines[0].len() as i64;
+    let cells = lines.concat();
+    let start = cells.find('D').unwrap();
+
+    // Looks up the tile at the position plus the command's offset through
+    // two patched operands, then moves there unless it is a wall
+    let mut program = format!(
+        "3,30,1001,30,33,8,1,31,0,32,1001,32,38,15,1001,0,0,33,4,33,\
+         1006,33,0,1001,32,0,31,1105,1,0,0,{},0,0",
+        start
+    );
+    for dir in &order {
+        let offset = match dir {
+            Dir4::North => -width,
+            Dir4::South => width,
+            Dir4::West => -1,
+            Dir4::East => 1,
+        };
+        program += &format!(",{}", offset);
+    }
+
+    for c in cells.chars() {
+        let status = match c {
+            '#' => 0,
+            '.' | 'D' => 1,
+            'O' => 2,
+            _ => 7,
+        };
+        program += &format!(",{}", status);
+    }
+
+    program
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAZE: &str = "\
+#######
+#D..#.#
+#.#.#.#
+#.#...#
+#.###O#
+#.....#
+#######";
+
+    const ORDER: [Dir4; 4] = [Dir4::North, Dir4::South, Dir4::West, Dir4::East];
+
+    /// Maps the robot's replies back onto the characters of the maze.
+    struct Probe {
+        order: [Dir4; 4],
+    }
+
+    impl MoveProtocol for Probe {
+        type Tile = char;
+
+        fn command(&self, dir: Dir4) -> i64 {
+            dir.code(self.order, 1)
+        }
+
+        fn tile(&self, status: i64) -> Option<char> {
+            match status {
+                0 => Some('#'),
+                1 => Some('.'),
+                2 => Some('O'),
+                _ => None,
+            }
+        }
+
+        fn passable(&self, tile: &char) -> bool {
+            *tile != '#'
+        }
+
+        fn start(&self) -> char {
+            'D'
+        }
+    }
+
+    fn explorer(maze: &str, order: [Dir4; 4]) -> Explorer<Probe> {
+        let computer = Computer::load(&maze_program(maze, order)).unwrap();
+        Explorer::new(computer, Probe { order })
+    }
+
+    /// Checks every open tile was found and that the map agrees with the maze.
+    fn assert_mapped(map: &SparseGrid<char>, maze: &str) {
+        let lines = maze.lines().collect::<Vec<_>>();
+        let y0 = lines.iter().position(|line| line.contains('D')).unwrap() as i64;
+        let x0 = lines[y0 as usize].find('D').unwrap() as i64;
+
+        for ((x, y), &tile) in map.iter() {
+            let expected = match lines[(y + y0) as usize].as_bytes()[(x + x0) as usize] {
+                // Walking back over the start records it as open
+                b'D' if tile == '.' => '.',
+                c => c as char,
+            };
+            assert_eq!(tile, expected, "at ({}, {})", x, y);
+        }
+
+        let open = maze.chars().filter(|&c| c != '#' && c != '\n').count();
+        assert_eq!(map.iter().filter(|(_, &tile)| tile != '#').count(), open);
+    }
+
+    #[test]
+    fn test_depth_first() {
+        let mut explorer = explorer(MAZE, ORDER);
+        explorer.explore(Strategy::DepthFirst).unwrap();
+
+        assert_eq!(explorer.position(), (0, 0));
+        assert_mapped(explorer.map(), MAZE);
+    }
+
+    #[test]
+    fn test_depth_first_deep() {
+        // Far deeper than the stack would allow one call per tile
+        let length = 100_000;
+        let wall = "#".repeat(length + 2);
+        let maze = format!("{}\n#D{}#\n{}", wall, ".".repeat(length - 1), wall);
+
+        let mut explorer = explorer(&maze, ORDER);
+        explorer.explore(Strategy::DepthFirst).unwrap();
+
+        assert_eq!(explorer.position(), (0, 0));
+        assert_eq!(explorer.map().get((length as i64 - 1, 0)), Some(&'.'));
+        assert_eq!(explorer.map().get((length as i64, 0)), Some(&'#'));
+        // Every tile is entered and left once, and each wall bumped into once
+        assert_eq!(explorer.moves(), 2 * (length - 1) + 2 * length + 2);
+    }
+
+    #[test]
+    fn test_nearest_frontier() {
+        let mut nearest = explorer(MAZE, ORDER);
+        nearest.explore(Strategy::NearestFrontier).unwrap();
+
+        assert_mapped(nearest.map(), MAZE);
+
+        let mut depth_first = explorer(MAZE, ORDER);
+        depth_first.explore(Strategy::DepthFirst).unwrap();
+
+        assert_eq!(nearest.into_map(), depth_first.into_map());
+    }
+
+    #[test]
+    fn test_protocol() {
+        let order = [Dir4::East, Dir4::West, Dir4::South, Dir4::North];
+        let mut explorer = explorer(MAZE, order);
+
+        assert_eq!(explorer.step(Dir4::North), Ok('#'));
+        assert_eq!(explorer.position(), (0, 0));
+        assert_eq!(explorer.step(Dir4::East), Ok('.'));
+        assert_eq!(explorer.position(), (1, 0));
+        assert_eq!(explorer.moves(), 2);
+
+        explorer.explore(Strategy::DepthFirst).unwrap();
+        assert_mapped(explorer.map(), MAZE);
+    }
+
+    #[test]
+    fn test_observe() {
+        let seen = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let mut explorer = explorer(MAZE, ORDER);
+
+        let log = seen.clone();
+        explorer.observe(move |map, droid| log.borrow_mut().push((map.iter().count(), droid)));
+
+        explorer.step(Dir4::South).unwrap();
+        explorer.step(Dir4::West).unwrap();
+
+        assert_eq!(*seen.borrow(), vec![(2, (0, 1)), (3, (0, 1))]);
+    }
+
+    #[test]
+    fn test_path() {
+        let mut explorer = explorer(MAZE, ORDER);
+
+        assert_eq!(explorer.path((0, 0), (4, 3)), None);
+
+        explorer.explore(Strategy::DepthFirst).unwrap();
+
+        use Dir4::*;
+        assert_eq!(
+            explorer.path((0, 0), (4, 3)),
+            Some(vec![East, East, South, South, East, East, South])
+        );
+        assert_eq!(explorer.path((4, 3), (4, 3)), Some(vec![]));
+        assert_eq!(explorer.path((0, 0), (-1, 0)), None);
+        assert_eq!(explorer.path((0, 0), (10, 10)), None);
+    }
+
+    #[test]
+    fn test_errors() {
+        let protocol = || Probe { order: ORDER };
+
+        let mut halted = Explorer::new(Computer::load("99").unwrap(), protocol());
+        assert_eq!(halted.step(Dir4::North), Err(ExploreError::Halted));
+
+        let mut broken = Explorer::new(Computer::load("3,0,42").unwrap(), protocol());
+        assert!(matches!(
+            broken.step(Dir4::North),
+            Err(ExploreError::Runtime(_))
+        ));
+
+        let mut chatty = Explorer::new(
+            Computer::load("3,100,104,1,104,1,1105,1,0").unwrap(),
+            protocol(),
+        );
+        assert_eq!(
+            chatty.step(Dir4::North),
+            Err(ExploreError::UnexpectedOutput(vec![1, 1]))
+        );
+
+        let mut unknown = explorer("####\n#D?#\n####", ORDER);
+        assert_eq!(
+            unknown.explore(Strategy::DepthFirst),
+            Err(ExploreError::UnexpectedOutput(vec![7]))
+        );
+        assert_eq!(unknown.moves(), 2);
+
+        let mut blocked = explorer(MAZE, ORDER);
+        assert_eq!(
+            blocked.walk(&[Dir4::East, Dir4::South]),
+            Err(ExploreError::Blocked((1, 1)))
+        );
+        assert_eq!(blocked.position(), (1, 0));
+    }
+}
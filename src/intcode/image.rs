@@ -0,0 +1,330 @@
+use crate::intcode::computer::State;
+use crate::intcode::parser::{int, token};
+use nom::branch::alt;
+use nom::character::complete::{alpha1, char};
+use nom::combinator::{all_consuming, map};
+use nom::multi::separated_list;
+use nom::sequence::{delimited, separated_pair};
+use nom::IResult;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display, Error, Formatter};
+use std::fs;
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"ICMI";
+const VERSION: u8 = 1;
+
+#[derive(Eq, PartialEq)]
+pub enum ImageError {
+    Io(String),
+    BadMagic,
+    UnsupportedVersion(u64),
+    Truncated,
+    Malformed(String),
+}
+
+impl Display for ImageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            ImageError::Io(error) => write!(f, "Error accessing image: {}", error),
+            ImageError::BadMagic => write!(f, "Not an intcode machine image"),
+            ImageError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported image version {}", version)
+            }
+            ImageError::Truncated => write!(f, "Image ends unexpectedly"),
+            ImageError::Malformed(reason) => write!(f, "Malformed image: {}", reason),
+        }
+    }
+}
+
+impl Debug for ImageError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        Display::fmt(self, f)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    Binary,
+    Json,
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    out.push(value as u8);
+}
+
+// Zigzag encoding keeps small negative values small
+fn write_signed(out: &mut Vec<u8>, value: i64) {
+    write_varint(out, ((value << 1) ^ (value >> 63)) as u64);
+}
+
+fn write_list(out: &mut Vec<u8>, values: &[i64]) {
+    write_varint(out, values.len() as u64);
+
+    for &value in values {
+        write_signed(out, value);
+    }
+}
+
+/// Binary image: magic bytes and a version, followed by `pc`, `rb`, the
+/// instruction count, memory, queued input and pending output as LEB128
+/// varints.
+pub fn encode(state: &State) -> Vec<u8> {
+    let mut out = MAGIC.to_vec();
+
+    out.push(VERSION);
+    write_varint(&mut out, state.pc as u64);
+    write_varint(&mut out, state.rb as u64);
+    write_varint(&mut out, state.instructions);
+    write_list(&mut out, &state.memory);
+    write_list(&mut out, &state.queue);
+    write_list(&mut out, &state.output);
+
+    out
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn varint(&mut self) -> Result<u64, ImageError> {
+        let mut value = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.bytes.split_first().ok_or(ImageError::Truncated)?;
+            self.bytes = rest;
+
+            value |= u64::from(byte & 0x7f) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(ImageError::Malformed("varint too long".to_string()))
+    }
+
+    fn signed(&mut self) -> Result<i64, ImageError> {
+        let value = self.varint()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn usize(&mut self) -> Result<usize, ImageError> {
+        usize::try_from(self.varint()?)
+            .map_err(|_| ImageError::Malformed("value out of range".to_string()))
+    }
+
+    fn list(&mut self) -> Result<Vec<i64>, ImageError> {
+        let len = self.usize()?;
+
+        // Every value takes at least one byte
+        if len > self.bytes.len() {
+            return Err(ImageError::Truncated);
+        }
+
+        (0..len).map(|_| self.signed()).collect()
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<State, ImageError> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(ImageError::BadMagic);
+    }
+
+    let mut reader = Reader {
+        bytes: &bytes[MAGIC.len()..],
+    };
+
+    let version = reader.varint()?;
+    if version != u64::from(VERSION) {
+        return Err(ImageError::UnsupportedVersion(version));
+    }
+
+    let state = State {
+        pc: reader.usize()?,
+        rb: reader.usize()?,
+        instructions: reader.varint()?,
+        memory: reader.list()?,
+        queue: reader.list()?,
+        output: reader.list()?,
+    };
+
+    if !reader.bytes.is_empty() {
+        return Err(ImageError::Malformed("trailing data".to_string()));
+    }
+
+    Ok(state)
+}
+
+fn join(values: &[i64]) -> String {
+    values
+        .iter()
+        .map(i64::to_string)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn to_json(state: &State) -> String {
+    format!(
+        "{{\n  \"version\": {},\n  \"pc\": {},\n  \"rb\": {},\n  \"instructions\": {},\n  \"memory\": [{}],\n  \"queue\": [{}],\n  \"output\": [{}]\n}}\n",
+        VERSION,
+        state.pc,
+        state.rb,
+        state.instructions,
+        join(&state.memory),
+        join(&state.queue),
+        join(&state.output)
+    )
+}
+
+enum Value {
+    Int(i64),
+    List(Vec<i64>),
+}
+
+fn value(input: &str) -> IResult<&str, Value> {
+    alt((
+        map(
+            delimited(
+                token(char('[')),
+                separated_list(token(char(',')), token(int)),
+                token(char(']')),
+            ),
+            Value::List,
+        ),
+        map(token(int), Value::Int),
+    ))(input)
+}
+
+fn field(input: &str) -> IResult<&str, (&str, Value)> {
+    separated_pair(
+        token(delimited(char('"'), alpha1, char('"'))),
+        char(':'),
+        value,
+    )(input)
+}
+
+fn object(input: &str) -> IResult<&str, Vec<(&str, Value)>> {
+    delimited(
+        token(char('{')),
+        separated_list(char(','), field),
+        token(char('}')),
+    )(input)
+}
+
+pub fn from_json(json: &str) -> Result<State, ImageError> {
+    let (_, fields) = all_consuming(object)(json)
+        .map_err(|_| ImageError::Malformed("invalid JSON".to_string()))?;
+    let mut fields = fields.into_iter().collect::<HashMap<_, _>>();
+
+    let mut int = |key: &str| match fields.remove(key) {
+        Some(Value::Int(value)) => Ok(value),
+        _ => Err(ImageError::Malformed(format!("expected number {}", key))),
+    };
+
+    let version = int("version")?;
+    if version != i64::from(VERSION) {
+        return Err(ImageError::UnsupportedVersion(version as u64));
+    }
+
+    let out_of_range = |_| ImageError::Malformed("value out of range".to_string());
+    let pc = usize::try_from(int("pc")?).map_err(out_of_range)?;
+    let rb = usize::try_from(int("rb")?).map_err(out_of_range)?;
+    let instructions = u64::try_from(int("instructions")?).map_err(out_of_range)?;
+
+    let mut list = |key: &str| match fields.remove(key) {
+        Some(Value::List(values)) => Ok(values),
+        _ => Err(ImageError::Malformed(format!("expected list {}", key))),
+    };
+
+    Ok(State {
+        memory: list("memory")?,
+        pc,
+        rb,
+        queue: list("queue")?,
+        output: list("output")?,
+        instructions,
+    })
+}
+
+pub fn save(state: &State, path: impl AsRef<Path>, format: Format) -> Result<(), ImageError> {
+    let bytes = match format {
+        Format::Binary => encode(state),
+        Format::Json => to_json(state).into_bytes(),
+    };
+
+    fs::write(path, bytes).map_err(|error| ImageError::Io(error.to_string()))
+}
+
+/// Loads an image in either format, telling them apart by the magic bytes.
+pub fn load(path: impl AsRef<Path>) -> Result<State, ImageError> {
+    let bytes = fs::read(path).map_err(|error| ImageError::Io(error.to_string()))?;
+
+    if bytes.starts_with(MAGIC) {
+        decode(&bytes)
+    } else {
+        let json = String::from_utf8(bytes).map_err(|_| ImageError::BadMagic)?;
+        from_json(&json)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::intcode::ascii::ECHO;
+    use crate::intcode::computer::{Computer, Status};
+
+    fn running_state() -> State {
+        let mut computer = Computer::load(ECHO).unwrap();
+
+        computer.push_input(-7);
+        computer.push_input(300);
+        assert_eq!(Ok(Status::AwaitingInput), computer.resume());
+        computer.push_input(42);
+
+        let state = computer.save_state();
+        assert_eq!(vec![-7, 300], state.output);
+
+        state
+    }
+
+    #[test]
+    fn test_binary() {
+        let state = running_state();
+        let bytes = encode(&state);
+
+        assert_eq!(Ok(state), decode(&bytes));
+        assert_eq!(
+            Err(ImageError::Truncated),
+            decode(&bytes[..bytes.len() - 1])
+        );
+        assert_eq!(Err(ImageError::BadMagic), decode(b"JUNK"));
+        assert_eq!(Err(ImageError::UnsupportedVersion(9)), decode(b"ICMI\x09"));
+    }
+
+    #[test]
+    fn test_json() {
+        let state = running_state();
+
+        assert_eq!(Ok(state.clone()), from_json(&to_json(&state)));
+        assert!(from_json("{\"version\": 1}").is_err());
+    }
+
+    #[test]
+    fn test_resume_from_image() {
+        let state = decode(&encode(&running_state())).unwrap();
+        let mut computer = Computer::from_state(state);
+
+        computer.push_input(10);
+
+        assert_eq!(Ok(Status::Halted), computer.resume());
+        assert_eq!(vec![-7, 300, 42, 10, 1000], computer.take_output());
+    }
+}
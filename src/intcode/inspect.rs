@@ -0,0 +1,199 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+/// A run of consecutive addresses whose values differ between two snapshots.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Change {
+    pub start: usize,
+    pub before: Vec<i64>,
+    pub after: Vec<i64>,
+}
+
+impl Change {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.start + self.after.len()
+    }
+}
+
+/// Compares two memory snapshots. Memory past the end of the shorter one is
+/// treated as zero, the same as the computer treats unwritten memory.
+pub fn diff(before: &[i64], after: &[i64]) -> Vec<Change> {
+    let at = |memory: &[i64], address: usize| memory.get(address).cloned().unwrap_or(0);
+
+    let mut changes: Vec<Change> = Vec::new();
+
+    for address in 0..before.len().max(after.len()) {
+        let (old, new) = (at(before, address), at(after, address));
+
+        if old == new {
+            continue;
+        }
+
+        match changes.last_mut() {
+            Some(change) if change.range().end == address => {
+                change.before.push(old);
+                change.after.push(new);
+            }
+            _ => changes.push(Change {
+                start: address,
+                before: vec![old],
+                after: vec![new],
+            }),
+        }
+    }
+
+    changes
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub address: usize,
+    /// Snapshots in which the address held exactly the observed value.
+    pub matches: usize,
+    /// Pearson correlation between the address and the observed values.
+    pub correlation: f64,
+}
+
+fn correlation(xs: &[f64], ys: &[f64]) -> f64 {
+    let n = xs.len() as f64;
+    let (mean_x, mean_y) = (xs.iter().sum::<f64>() / n, ys.iter().sum::<f64>() / n);
+
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in xs.iter().zip(ys) {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x) * (x - mean_x);
+        var_y += (y - mean_y) * (y - mean_y);
+    }
+
+    if var_x == 0.0 || var_y == 0.0 {
+        0.0
+    } else {
+        cov / (var_x * var_y).sqrt()
+    }
+}
+
+/// Guesses which addresses hold a value the program reports, such as a score
+/// or a position, given snapshots of memory and the value observed at each.
+/// Addresses that hold the value exactly come first, then those that merely
+/// move with it; addresses with no match and weak correlation are dropped, as
+/// are addresses that never change while the observed value does.
+///
+/// Panics if there is not one observed value per snapshot.
+pub fn find_variable<M: AsRef<[i64]>>(snapshots: &[M], observed: &[i64]) -> Vec<Candidate> {
+    assert_eq!(
+        snapshots.len(),
+        observed.len(),
+        "Expected one observed value per snapshot"
+    );
+
+    let varies = observed.windows(2).any(|pair| pair[0] != pair[1]);
+    let len = snapshots
+        .iter()
+        .map(|memory| memory.as_ref().len())
+        .max()
+        .unwrap_or(0);
+    let ys = observed.iter().map(|&y| y as f64).collect::<Vec<_>>();
+
+    let mut candidates = (0..len)
+        .filter_map(|address| {
+            let values = snapshots
+                .iter()
+                .map(|memory| memory.as_ref().get(address).cloned().unwrap_or(0))
+                .collect::<Vec<_>>();
+
+            // Matching a value that never changes, like an unused zero, says nothing
+            let constant = values.windows(2).all(|pair| pair[0] == pair[1]);
+            if constant && varies {
+                return None;
+            }
+
+            let matches = values.iter().zip(observed).filter(|(a, b)| a == b).count();
+            let xs = values.iter().map(|&x| x as f64).collect::<Vec<_>>();
+            let correlation = correlation(&xs, &ys);
+
+            if matches > 0 || correlation.abs() >= 0.9 {
+                Some(Candidate {
+                    address,
+                    matches,
+                    correlation,
+                })
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    candidates.sort_by(|a, b| {
+        b.matches.cmp(&a.matches).then(
+            b.correlation
+                .abs()
+                .partial_cmp(&a.correlation.abs())
+                .unwrap_or(Ordering::Equal),
+        )
+    });
+
+    candidates
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let before = vec![1, 2, 3, 4, 5];
+        let after = vec![1, 9, 9, 4, 0, 0, 7];
+
+        assert_eq!(
+            diff(&before, &after),
+            vec![
+                Change {
+                    start: 1,
+                    before: vec![2, 3],
+                    after: vec![9, 9],
+                },
+                Change {
+                    start: 4,
+                    before: vec![5],
+                    after: vec![0],
+                },
+                Change {
+                    start: 6,
+                    before: vec![0],
+                    after: vec![7],
+                },
+            ]
+        );
+        assert!(diff(&before, &[1, 2, 3, 4, 5, 0]).is_empty());
+    }
+
+    #[test]
+    fn test_find_variable() {
+        // Address 2 holds the score, address 0 is twice it, and address 4 is
+        // always zero like the score at the start
+        let snapshots = vec![
+            vec![0, 7, 0, 1, 0],
+            vec![4, 7, 2, 1, 0],
+            vec![10, 7, 5, 8, 0],
+        ];
+        let candidates = find_variable(&snapshots, &[0, 2, 5]);
+
+        assert_eq!(2, candidates[0].address);
+        assert_eq!(3, candidates[0].matches);
+        assert_eq!(0, candidates[1].address);
+        assert!(candidates.iter().all(|c| c.address != 1 && c.address != 4));
+
+        // With nothing changing, the constant match is all there is to go on
+        let candidates = find_variable(&snapshots[..1], &[7]);
+        assert_eq!(
+            vec![1],
+            candidates.iter().map(|c| c.address).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "one observed value per snapshot")]
+    fn test_find_variable_lengths() {
+        find_variable(&[vec![1, 2], vec![3, 4]], &[1]);
+    }
+}
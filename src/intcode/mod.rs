@@ -1,2 +1,11 @@
+pub mod adventure;
+pub mod ascii;
+pub mod asynchronous;
 pub mod computer;
+pub mod explorer;
+pub mod image;
+pub mod inspect;
+pub mod movement;
+pub mod oracle;
 pub mod parser;
+pub mod springscript;
@@ -0,0 +1,249 @@
+use std::collections::HashSet;
+
+const MAX_FUNCTIONS: usize = 3;
+const MAX_LENGTH: usize = 20;
+const NAMES: [&str; MAX_FUNCTIONS] = ["A", "B", "C"];
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Routines {
+    pub main: Vec<usize>,
+    pub functions: Vec<Vec<String>>,
+}
+
+impl Routines {
+    pub fn main_routine(&self) -> String {
+        self.main
+            .iter()
+            .map(|&i| NAMES[i])
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    pub fn function(&self, i: usize) -> String {
+        self.functions
+            .get(i)
+            .map(|f| f.join(","))
+            .unwrap_or_default()
+    }
+
+    /// Lines to send to the robot: the main routine, each function, and the
+    /// answer to whether a continuous video feed is wanted.
+    pub fn input(&self, video_feed: bool) -> Vec<String> {
+        let mut lines = vec![self.main_routine()];
+
+        lines.extend((0..MAX_FUNCTIONS).map(|i| self.function(i)));
+        lines.push(if video_feed { "y" } else { "n" }.to_string());
+
+        lines
+    }
+
+    pub fn expand(&self) -> String {
+        self.main
+            .iter()
+            .map(|&i| self.function(i))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+fn encoded_len(moves: &[String]) -> usize {
+    moves.iter().map(String::len).sum::<usize>() + moves.len().saturating_sub(1)
+}
+
+fn search(
+    moves: &[String],
+    start: usize,
+    functions: &mut Vec<Vec<String>>,
+    main: &mut Vec<usize>,
+) -> bool {
+    if start == moves.len() {
+        return true;
+    }
+
+    // Each call adds a name and a comma to the main routine
+    if main.len() * 2 + 1 > MAX_LENGTH {
+        return false;
+    }
+
+    for i in 0..functions.len() {
+        if moves[start..].starts_with(&functions[i]) {
+            main.push(i);
+
+            if search(moves, start + functions[i].len(), functions, main) {
+                return true;
+            }
+
+            main.pop();
+        }
+    }
+
+    if functions.len() < MAX_FUNCTIONS {
+        for end in start + 1..=moves.len() {
+            let function = &moves[start..end];
+
+            if encoded_len(function) > MAX_LENGTH {
+                break;
+            }
+
+            functions.push(function.to_vec());
+            main.push(functions.len() - 1);
+
+            if search(moves, end, functions, main) {
+                return true;
+            }
+
+            main.pop();
+            functions.pop();
+        }
+    }
+
+    false
+}
+
+/// Factors a comma separated path such as `R,8,R,8,L,6` into a main routine
+/// calling up to three functions, each at most twenty characters long.
+pub fn compress(path: &str) -> Option<Routines> {
+    let moves = path
+        .split(',')
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+
+    let mut functions = Vec::new();
+    let mut main = Vec::new();
+
+    if search(&moves, 0, &mut functions, &mut main) {
+        Some(Routines { main, functions })
+    } else {
+        None
+    }
+}
+
+/// Follows the scaffold from the robot, going straight for as long as
+/// possible and only turning at the ends of each segment. A robot facing
+/// away from the scaffold turns around with `R,R`. `None` if there is no
+/// robot or no scaffold next to it.
+pub fn scaffold_path(map: &str) -> Option<String> {
+    let mut scaffold = HashSet::new();
+    let mut robot = None;
+
+    for (y, line) in map.lines().enumerate() {
+        for (x, c) in line.chars().enumerate() {
+            let pos = (x as i64, y as i64);
+            let dir = match c {
+                '^' => Some((0, -1)),
+                'v' => Some((0, 1)),
+                '<' => Some((-1, 0)),
+                '>' => Some((1, 0)),
+                '#' => None,
+                _ => continue,
+            };
+
+            scaffold.insert(pos);
+            if let Some(dir) = dir {
+                robot = Some((pos, dir));
+            }
+        }
+    }
+
+    let ((mut x, mut y), (mut dx, mut dy)) = robot?;
+    let mut moves = Vec::new();
+
+    loop {
+        let open = |(dx, dy)| scaffold.contains(&(x + dx, y + dy));
+        let (left, right, back) = ((dy, -dx), (-dy, dx), (-dx, -dy));
+
+        // Only the first segment may go straight on or back the way the robot
+        // faces, as every later one starts where the last could go no further
+        let (turns, (ndx, ndy)): (&[&str], _) = if moves.is_empty() && open((dx, dy)) {
+            (&[], (dx, dy))
+        } else if open(left) {
+            (&["L"], left)
+        } else if open(right) {
+            (&["R"], right)
+        } else if moves.is_empty() && open(back) {
+            (&["R", "R"], back)
+        } else {
+            break;
+        };
+
+        dx = ndx;
+        dy = ndy;
+
+        let mut steps = 0;
+        while scaffold.contains(&(x + dx, y + dy)) {
+            x += dx;
+            y += dy;
+            steps += 1;
+        }
+
+        moves.extend(turns.iter().map(|turn| turn.to_string()));
+        moves.push(steps.to_string());
+    }
+
+    if moves.is_empty() {
+        None
+    } else {
+        Some(moves.join(","))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PATH: &str = "R,8,R,8,R,4,R,4,R,8,L,6,L,2,R,4,R,4,R,8,R,8,R,8,L,6,L,2";
+
+    #[test]
+    fn test_scaffold_path() {
+        let map = "\
+#######...#####
+#.....#...#...#
+#.....#...#...#
+......#...#...#
+......#...###.#
+......#.....#.#
+^########...#.#
+......#.#...#.#
+......#########
+........#...#..
+....#########..
+....#...#......
+....#...#......
+....#...#......
+....#####......";
+
+        assert_eq!(Some(PATH.to_string()), scaffold_path(map));
+    }
+
+    #[test]
+    fn test_scaffold_path_start() {
+        assert_eq!(Some("4".to_string()), scaffold_path("v\n#\n#\n#\n#"));
+        assert_eq!(
+            Some("4,L,2".to_string()),
+            scaffold_path("v..\n#..\n#..\n#..\n###")
+        );
+        assert_eq!(Some("R,R,2".to_string()), scaffold_path("^\n#\n#"));
+        assert_eq!(None, scaffold_path("..#\n.>.\n..."));
+        assert_eq!(None, scaffold_path("###\n..."));
+    }
+
+    #[test]
+    fn test_compress() {
+        let routines = compress(PATH).unwrap();
+
+        assert_eq!(PATH, routines.expand());
+        assert!(routines.functions.len() <= MAX_FUNCTIONS);
+
+        for line in routines.input(false) {
+            assert!(line.len() <= MAX_LENGTH);
+        }
+    }
+
+    #[test]
+    fn test_incompressible() {
+        let path = (1..=30).map(|i| format!("L,{}", i)).collect::<Vec<_>>();
+        assert_eq!(None, compress(&path.join(",")));
+    }
+}
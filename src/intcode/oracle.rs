@@ -0,0 +1,191 @@
+use crate::grid::SparseGrid;
+use crate::intcode::computer::{Computer, RuntimeError, State, Status};
+use crate::intcode::parser::ParseError;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::sync::Mutex;
+use std::thread;
+
+enum Mode {
+    Input,
+    Memory { parameters: Vec<i64>, result: i64 },
+}
+
+/// A program treated as a pure function: every query runs on a fresh copy of
+/// the memory it was loaded with.
+pub struct Oracle {
+    pristine: State,
+    mode: Mode,
+    cache: Mutex<HashMap<Vec<i64>, Vec<i64>>>,
+}
+
+impl Oracle {
+    fn new(program: &str, mode: Mode) -> Result<Oracle, ParseError<'_>> {
+        let pristine = Computer::load(program)?.save_state();
+
+        Ok(Oracle {
+            pristine,
+            mode,
+            cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Queries are fed as input, and answered with everything output.
+    pub fn load(program: &str) -> Result<Oracle, ParseError<'_>> {
+        Oracle::new(program, Mode::Input)
+    }
+
+    /// Queries are written to the `parameters` addresses before running, and
+    /// answered with the value left at the `result` address.
+    pub fn load_patched<'a>(
+        program: &'a str,
+        parameters: &[i64],
+        result: i64,
+    ) -> Result<Oracle, ParseError<'a>> {
+        let mode = Mode::Memory {
+            parameters: parameters.to_vec(),
+            result,
+        };
+
+        Oracle::new(program, mode)
+    }
+
+    /// Answers a query without looking it up in or adding it to the cache, for
+    /// searches that never ask the same thing twice.
+    pub fn evaluate(&self, query: &[i64]) -> Result<Vec<i64>, RuntimeError> {
+        let mut computer = Computer::from_state(self.pristine.clone());
+
+        match &self.mode {
+            Mode::Input => query.iter().for_each(|&value| computer.push_input(value)),
+            Mode::Memory { parameters, .. } => {
+                for (&address, &value) in parameters.iter().zip(query) {
+                    computer.set(address, value)?;
+                }
+            }
+        }
+
+        if computer.resume()? == Status::AwaitingInput {
+            return Err(RuntimeError::InputError);
+        }
+
+        match &self.mode {
+            Mode::Input => Ok(computer.take_output()),
+            Mode::Memory { result, .. } => Ok(vec![computer.get(*result)?]),
+        }
+    }
+
+    pub fn query(&self, query: &[i64]) -> Result<Vec<i64>, RuntimeError> {
+        if let Some(result) = self.cache.lock().unwrap().get(query) {
+            return Ok(result.clone());
+        }
+
+        let result = self.evaluate(query)?;
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(query.to_vec(), result.clone());
+
+        Ok(result)
+    }
+
+    pub fn cached(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    /// Answers every query, spread across `threads` worker threads. Results
+    /// are in the same order as the queries.
+    pub fn query_all(
+        &self,
+        queries: &[Vec<i64>],
+        threads: usize,
+    ) -> Result<Vec<Vec<i64>>, RuntimeError> {
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = channel();
+
+        thread::scope(|scope| {
+            for _ in 0..threads.max(1) {
+                let sender = sender.clone();
+                let next = &next;
+
+                scope.spawn(move || loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+
+                    if i >= queries.len() || sender.send((i, self.query(&queries[i]))).is_err() {
+                        break;
+                    }
+                });
+            }
+        });
+
+        drop(sender);
+
+        let mut results = vec![Vec::new(); queries.len()];
+        for (i, result) in receiver {
+            results[i] = result?;
+        }
+
+        Ok(results)
+    }
+
+    /// Queries every `(x, y)` in the given area, recording the first value
+    /// output for each.
+    pub fn scan(
+        &self,
+        width: i64,
+        height: i64,
+        threads: usize,
+    ) -> Result<SparseGrid<i64>, RuntimeError> {
+        let queries = (0..height)
+            .flat_map(|y| (0..width).map(move |x| vec![x, y]))
+            .collect::<Vec<_>>();
+
+        let mut grid = SparseGrid::new();
+
+        for (query, result) in queries.iter().zip(self.query_all(&queries, threads)?) {
+            let &value = result.first().ok_or(RuntimeError::OutputError)?;
+            grid.insert((query[0], query[1]), value);
+        }
+
+        Ok(grid)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Outputs the sum of two inputs
+    const SUM: &str = "3,20,3,21,1,20,21,22,4,22,99";
+
+    #[test]
+    fn test_query() {
+        let oracle = Oracle::load(SUM).unwrap();
+
+        assert_eq!(Ok(vec![5]), oracle.query(&[2, 3]));
+        assert_eq!(Ok(vec![5]), oracle.query(&[2, 3]));
+        assert_eq!(Ok(vec![-1]), oracle.query(&[2, -3]));
+        assert_eq!(2, oracle.cached());
+        assert_eq!(Err(RuntimeError::InputError), oracle.query(&[2]));
+    }
+
+    #[test]
+    fn test_load_patched() {
+        let oracle = Oracle::load_patched("1,0,0,0,99", &[1, 2], 0).unwrap();
+
+        assert_eq!(Ok(vec![198]), oracle.query(&[4, 4]));
+        assert_eq!(Ok(vec![2]), oracle.query(&[0, 0]));
+        assert_eq!(Ok(vec![100]), oracle.evaluate(&[0, 4]));
+        assert_eq!(2, oracle.cached());
+    }
+
+    #[test]
+    fn test_scan() {
+        let oracle = Oracle::load(SUM).unwrap();
+        let grid = oracle.scan(5, 4, 3).unwrap();
+
+        assert_eq!(20, grid.len());
+        assert_eq!(Some(&7), grid.get((4, 3)));
+        assert_eq!(20, oracle.cached());
+    }
+}
@@ -1,14 +1,21 @@
 use nom::{Err, IResult};
-use nom::character::complete::{digit1, line_ending, char};
+use nom::character::complete::{digit1, line_ending, char, multispace0};
 use nom::combinator::{all_consuming, map_res, opt, recognize};
 use nom::error::ErrorKind;
 use nom::multi::separated_list;
-use nom::sequence::tuple;
+use nom::sequence::{delimited, tuple};
 
-fn int(input: &str) -> IResult<&str, i64> {
+pub(crate) fn int(input: &str) -> IResult<&str, i64> {
     map_res(recognize(tuple((opt(char('-')), digit1))), str::parse)(input)
 }
 
+/// Skips whitespace either side of what `parser` matches.
+pub(crate) fn token<'a, O>(
+    parser: impl Fn(&'a str) -> IResult<&'a str, O>,
+) -> impl Fn(&'a str) -> IResult<&'a str, O> {
+    delimited(multispace0, parser, multispace0)
+}
+
 fn ints(input: &str) -> IResult<&str, Vec<i64>> {
     separated_list(char(','), int)(input)
 }
@@ -19,3 +26,17 @@ pub fn read_program(program: &str) -> Result<Vec<i64>, ParseError> {
     all_consuming(tuple((ints, opt(line_ending))))(program)
         .map(|(_, (result, _))| result)
 }
+
+/// Where in `program` an error happened, as a character offset to the value
+/// that failed to parse, along with a few characters of what was found there.
+pub fn error_position<'a>(program: &'a str, error: &ParseError<'a>) -> (usize, &'a str) {
+    let rest = match error {
+        Err::Error((rest, _)) | Err::Failure((rest, _)) => rest,
+        Err::Incomplete(_) => "",
+    };
+    let rest = rest.strip_prefix(',').unwrap_or(rest);
+    let found = rest.char_indices().nth(10).map_or(rest, |(end, _)| &rest[..end]);
+    let offset = program[..program.len() - rest.len()].chars().count();
+
+    (offset, found)
+}
@@ -0,0 +1,487 @@
+use crate::intcode::ascii::Ascii;
+use crate::intcode::computer::RuntimeError;
+use crate::intcode::parser::{error_position, token};
+use nom::branch::alt;
+use nom::character::complete::{char, one_of};
+use nom::combinator::{all_consuming, map};
+use nom::multi::many0;
+use nom::sequence::{delimited, pair, preceded};
+use nom::IResult;
+use std::collections::BTreeSet;
+use std::fmt::{Debug, Display, Error, Formatter};
+
+const MAX_INSTRUCTIONS: usize = 15;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Expr {
+    Sensor(char),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn eval(&self, sensor: &impl Fn(char) -> bool) -> bool {
+        match self {
+            Expr::Sensor(register) => sensor(*register),
+            Expr::Not(expr) => !expr.eval(sensor),
+            Expr::And(a, b) => a.eval(sensor) && b.eval(sensor),
+            Expr::Or(a, b) => a.eval(sensor) || b.eval(sensor),
+        }
+    }
+
+    fn sensors(&self, result: &mut BTreeSet<char>) {
+        match self {
+            Expr::Sensor(register) => {
+                result.insert(*register);
+            }
+            Expr::Not(expr) => expr.sensors(result),
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                a.sensors(result);
+                b.sensors(result);
+            }
+        }
+    }
+}
+
+fn sensor(input: &str) -> IResult<&str, Expr> {
+    map(token(one_of("ABCDEFGHIJKLMNOPQRSTUVWXYZ")), Expr::Sensor)(input)
+}
+
+fn unary(input: &str) -> IResult<&str, Expr> {
+    alt((
+        map(preceded(token(char('!')), unary), |expr| {
+            Expr::Not(Box::new(expr))
+        }),
+        delimited(token(char('(')), or_expr, token(char(')'))),
+        sensor,
+    ))(input)
+}
+
+fn and_expr(input: &str) -> IResult<&str, Expr> {
+    map(
+        pair(unary, many0(preceded(token(char('&')), unary))),
+        |(first, rest)| {
+            rest.into_iter()
+                .fold(first, |a, b| Expr::And(Box::new(a), Box::new(b)))
+        },
+    )(input)
+}
+
+fn or_expr(input: &str) -> IResult<&str, Expr> {
+    map(
+        pair(and_expr, many0(preceded(token(char('|')), and_expr))),
+        |(first, rest)| {
+            rest.into_iter()
+                .fold(first, |a, b| Expr::Or(Box::new(a), Box::new(b)))
+        },
+    )(input)
+}
+
+/// Parses formulas such as `!(A & B & C) & D`, with `!` binding tightest,
+/// then `&`, then `|`.
+pub fn parse_formula(input: &str) -> Result<Expr, SpringError> {
+    all_consuming(or_expr)(input)
+        .map(|(_, expr)| expr)
+        .map_err(|_| SpringError::Parse(input.to_string()))
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Mode {
+    Walk,
+    Run,
+}
+
+impl Mode {
+    pub fn sensors(self) -> &'static str {
+        match self {
+            Mode::Walk => "ABCD",
+            Mode::Run => "ABCDEFGHI",
+        }
+    }
+
+    pub fn command(self) -> &'static str {
+        match self {
+            Mode::Walk => "WALK",
+            Mode::Run => "RUN",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Op {
+    And,
+    Or,
+    Not,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Instruction {
+    pub op: Op,
+    pub x: char,
+    pub y: char,
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        let op = match self.op {
+            Op::And => "AND",
+            Op::Or => "OR",
+            Op::Not => "NOT",
+        };
+
+        write!(f, "{} {} {}", op, self.x, self.y)
+    }
+}
+
+fn ins(op: Op, x: char, y: char) -> Instruction {
+    Instruction { op, x, y }
+}
+
+/// Evaluates a springscript program the way the droid does, with `T` and `J`
+/// starting out false. Returns whether the droid jumps.
+pub fn simulate(program: &[Instruction], sensor: impl Fn(char) -> bool) -> bool {
+    let (mut t, mut j) = (false, false);
+
+    for instruction in program {
+        let x = match instruction.x {
+            'T' => t,
+            'J' => j,
+            register => sensor(register),
+        };
+
+        let y = match instruction.y {
+            'T' => &mut t,
+            _ => &mut j,
+        };
+
+        *y = match instruction.op {
+            Op::And => x && *y,
+            Op::Or => x || *y,
+            Op::Not => !x,
+        };
+    }
+
+    j
+}
+
+// Implicant over the formula's sensors: bits set in `mask` don't matter
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+struct Implicant {
+    value: u32,
+    mask: u32,
+}
+
+impl Implicant {
+    fn covers(self, minterm: u32) -> bool {
+        minterm & !self.mask == self.value
+    }
+
+    fn literals(self, sensors: &[char]) -> Vec<(char, bool)> {
+        sensors
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| self.mask & (1 << i) == 0)
+            .map(|(i, &sensor)| (sensor, self.value & (1 << i) != 0))
+            .collect()
+    }
+}
+
+fn prime_implicants(minterms: &[u32]) -> Vec<Implicant> {
+    let mut current = minterms
+        .iter()
+        .map(|&value| Implicant { value, mask: 0 })
+        .collect::<BTreeSet<_>>();
+    let mut primes = BTreeSet::new();
+
+    while !current.is_empty() {
+        let mut next = BTreeSet::new();
+        let mut combined = BTreeSet::new();
+
+        for &a in &current {
+            for &b in &current {
+                let diff = a.value ^ b.value;
+
+                if a.mask == b.mask && diff.count_ones() == 1 && a.value < b.value {
+                    next.insert(Implicant {
+                        value: a.value & !diff,
+                        mask: a.mask | diff,
+                    });
+                    combined.insert(a);
+                    combined.insert(b);
+                }
+            }
+        }
+
+        primes.extend(current.difference(&combined).cloned());
+        current = next;
+    }
+
+    primes.into_iter().collect()
+}
+
+/// Quine-McCluskey: essential prime implicants first, then greedily the prime
+/// covering the most remaining minterms.
+fn minimize(minterms: &[u32]) -> Vec<Implicant> {
+    let primes = prime_implicants(minterms);
+    let mut remaining = minterms.iter().cloned().collect::<BTreeSet<_>>();
+    let mut cover = Vec::new();
+
+    for &minterm in minterms {
+        let mut covering = primes.iter().filter(|prime| prime.covers(minterm));
+
+        if let (Some(&prime), None) = (covering.next(), covering.next()) {
+            if !cover.contains(&prime) {
+                cover.push(prime);
+                remaining.retain(|&m| !prime.covers(m));
+            }
+        }
+    }
+
+    while !remaining.is_empty() {
+        let &best = primes
+            .iter()
+            .max_by_key(|prime| {
+                let covered = remaining.iter().filter(|&&m| prime.covers(m)).count();
+                (covered, prime.mask.count_ones())
+            })
+            .unwrap();
+
+        cover.push(best);
+        remaining.retain(|&m| !best.covers(m));
+    }
+
+    cover
+}
+
+// Computes a conjunction of literals into `register`. Negated literals are
+// gathered as !(n1 | n2 | ...) so no second scratch register is needed.
+fn compile_term(literals: &[(char, bool)], register: char, program: &mut Vec<Instruction>) {
+    let positive = literals
+        .iter()
+        .filter(|l| l.1)
+        .map(|l| l.0)
+        .collect::<Vec<_>>();
+    let negative = literals
+        .iter()
+        .filter(|l| !l.1)
+        .map(|l| l.0)
+        .collect::<Vec<_>>();
+
+    match (negative.split_first(), positive.split_first()) {
+        (Some((&n, [])), _) => program.push(ins(Op::Not, n, register)),
+        (Some((&n, rest)), _) => {
+            program.push(ins(Op::Not, n, register));
+            program.push(ins(Op::Not, register, register));
+
+            for &n in rest {
+                program.push(ins(Op::Or, n, register));
+            }
+
+            program.push(ins(Op::Not, register, register));
+        }
+        (None, Some((&p, _))) => {
+            program.push(ins(Op::Not, p, register));
+            program.push(ins(Op::Not, register, register));
+        }
+        // An empty term is always true, and T is false until written
+        (None, None) => program.push(ins(Op::Not, 'T', register)),
+    }
+
+    let skip = if negative.is_empty() { 1 } else { 0 };
+    for &p in positive.iter().skip(skip) {
+        program.push(ins(Op::And, p, register));
+    }
+}
+
+fn compile_cover(cover: &[Implicant], sensors: &[char]) -> Vec<Instruction> {
+    let mut program = Vec::new();
+
+    for (i, implicant) in cover.iter().enumerate() {
+        let literals = implicant.literals(sensors);
+
+        if i == 0 {
+            compile_term(&literals, 'J', &mut program);
+        } else {
+            compile_term(&literals, 'T', &mut program);
+            program.push(ins(Op::Or, 'T', 'J'));
+        }
+    }
+
+    program
+}
+
+/// Compiles a formula deciding when to jump into the shortest program found
+/// by minimizing either the formula or its negation.
+pub fn compile(formula: &Expr, mode: Mode) -> Result<Vec<Instruction>, SpringError> {
+    let mut sensors = BTreeSet::new();
+    formula.sensors(&mut sensors);
+
+    if let Some(&register) = sensors.iter().find(|&&s| !mode.sensors().contains(s)) {
+        return Err(SpringError::UnknownRegister(register));
+    }
+
+    let sensors = sensors.into_iter().collect::<Vec<_>>();
+    let (ones, zeros): (Vec<u32>, Vec<u32>) = (0..1 << sensors.len()).partition(|&row| {
+        formula.eval(&|register| {
+            let i = sensors.iter().position(|&s| s == register).unwrap();
+            row & (1 << i) != 0
+        })
+    });
+
+    let direct = compile_cover(&minimize(&ones), &sensors);
+
+    let mut inverted = compile_cover(&minimize(&zeros), &sensors);
+    inverted.push(ins(Op::Not, 'J', 'J'));
+
+    let program = if inverted.len() < direct.len() {
+        inverted
+    } else {
+        direct
+    };
+
+    if program.len() > MAX_INSTRUCTIONS {
+        Err(SpringError::TooLong(program.len()))
+    } else {
+        Ok(program)
+    }
+}
+
+#[derive(Eq, PartialEq)]
+pub enum SpringError {
+    Parse(String),
+    /// The droid's program stopped parsing at a character offset, with what
+    /// was found there.
+    Program(usize, String),
+    UnknownRegister(char),
+    TooLong(usize),
+    Runtime(RuntimeError),
+    Fell(String),
+}
+
+impl Display for SpringError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            SpringError::Parse(formula) => write!(f, "Unable to parse formula {}", formula),
+            SpringError::Program(position, found) => write!(
+                f,
+                "Unable to parse the droid's program at character {}, found {:?}",
+                position, found
+            ),
+            SpringError::UnknownRegister(register) => {
+                write!(f, "Register {} is not available in this mode", register)
+            }
+            SpringError::TooLong(len) => write!(
+                f,
+                "Program needs {} instructions, only {} are allowed",
+                len, MAX_INSTRUCTIONS
+            ),
+            SpringError::Runtime(error) => write!(f, "{}", error),
+            SpringError::Fell(rendering) => write!(f, "Droid fell into space:\n{}", rendering),
+        }
+    }
+}
+
+impl Debug for SpringError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        Display::fmt(self, f)
+    }
+}
+
+impl From<RuntimeError> for SpringError {
+    fn from(error: RuntimeError) -> Self {
+        SpringError::Runtime(error)
+    }
+}
+
+/// Loads the droid's program, sends it the compiled formula, and returns the
+/// hull damage reported, or the droid's last moments if it fell.
+pub fn survey(program: &str, formula: &str, mode: Mode) -> Result<i64, SpringError> {
+    let script = compile(&parse_formula(formula)?, mode)?;
+    let mut ascii = Ascii::load(program).map_err(|error| {
+        let (position, found) = error_position(program, &error);
+        SpringError::Program(position, found.to_string())
+    })?;
+
+    let lines = script
+        .iter()
+        .map(Instruction::to_string)
+        .chain(Some(mode.command().to_string()))
+        .collect::<Vec<_>>();
+
+    let (_, output) = ascii.execute(lines.iter().map(String::as_str))?;
+
+    output
+        .answer()
+        .ok_or_else(|| SpringError::Fell(output.text()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_compiles(formula: &str, mode: Mode) {
+        let expr = parse_formula(formula).unwrap();
+        let program = compile(&expr, mode).unwrap();
+
+        for row in 0..1 << mode.sensors().len() {
+            let sensor = |register: char| {
+                let i = mode.sensors().find(register).unwrap();
+                row & (1 << i) != 0
+            };
+
+            assert_eq!(
+                expr.eval(&sensor),
+                simulate(&program, sensor),
+                "{}",
+                formula
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_formula() {
+        assert_eq!(
+            parse_formula("!A & (B | C)").unwrap(),
+            Expr::And(
+                Box::new(Expr::Not(Box::new(Expr::Sensor('A')))),
+                Box::new(Expr::Or(
+                    Box::new(Expr::Sensor('B')),
+                    Box::new(Expr::Sensor('C'))
+                ))
+            )
+        );
+        assert!(parse_formula("A &").is_err());
+    }
+
+    #[test]
+    fn test_compile() {
+        assert_compiles("!(A & B & C) & D", Mode::Walk);
+        assert_compiles("!(A & B & C) & D & (E | H)", Mode::Run);
+        assert_compiles("A | !A", Mode::Walk);
+        assert_compiles("A & !A", Mode::Walk);
+        assert_compiles("!A & !B | C & !D", Mode::Walk);
+    }
+
+    #[test]
+    fn test_compile_errors() {
+        let expr = parse_formula("A & E").unwrap();
+        assert_eq!(
+            Err(SpringError::UnknownRegister('E')),
+            compile(&expr, Mode::Walk)
+        );
+    }
+
+    #[test]
+    fn test_instruction_display() {
+        assert_eq!("NOT A J", ins(Op::Not, 'A', 'J').to_string());
+    }
+
+    #[test]
+    fn test_program_error() {
+        assert_eq!(
+            Err(SpringError::Program(4, "x,99".to_string())),
+            survey("1,2,x,99", "A", Mode::Walk)
+        );
+    }
+}
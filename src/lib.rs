@@ -1,6 +1,13 @@
 use aoc_runner_derive::aoc_lib;
 
-mod intcode;
+pub mod animation;
+pub mod export;
+pub mod geometry;
+pub mod grid;
+pub mod intcode;
+pub mod ocr;
+pub mod recording;
+pub mod sif;
 
 mod day1;
 mod day2;
@@ -0,0 +1,349 @@
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display, Error, Formatter};
+use std::ops::Range;
+
+type Font = &'static [(char, &'static [&'static str])];
+
+const SMALL: Font = &[
+    ('A', &[".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', &["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', &[".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', &["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', &["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', &[".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', &["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', &["###", ".#.", ".#.", ".#.", ".#.", "###"]),
+    ('J', &["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', &["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', &["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', &[".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', &["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', &["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', &[".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', &["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', &["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', &["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+const LARGE: Font = &[
+    (
+        'A',
+        &[
+            "..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'B',
+        &[
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#",
+            "#....#", "#####.",
+        ],
+    ),
+    (
+        'C',
+        &[
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+            "#....#", ".####.",
+        ],
+    ),
+    (
+        'E',
+        &[
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+    (
+        'F',
+        &[
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "#.....",
+        ],
+    ),
+    (
+        'G',
+        &[
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#",
+            "#...##", ".###.#",
+        ],
+    ),
+    (
+        'H',
+        &[
+            "#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'J',
+        &[
+            "...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.",
+            "#...#.", ".###..",
+        ],
+    ),
+    (
+        'K',
+        &[
+            "#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..",
+            "#...#.", "#....#",
+        ],
+    ),
+    (
+        'L',
+        &[
+            "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+    (
+        'N',
+        &[
+            "#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##",
+            "#...##", "#....#",
+        ],
+    ),
+    (
+        'P',
+        &[
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "#.....",
+        ],
+    ),
+    (
+        'R',
+        &[
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'X',
+        &[
+            "#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'Z',
+        &[
+            "######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+];
+
+#[derive(Eq, PartialEq)]
+pub enum OcrError {
+    Empty,
+    UnsupportedHeight(usize),
+    /// The glyph at `index`, drawn with `#` and `.`, matched no letter.
+    UnknownGlyph {
+        index: usize,
+        glyph: String,
+    },
+}
+
+impl Display for OcrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            OcrError::Empty => write!(f, "No letters to read"),
+            OcrError::UnsupportedHeight(height) => {
+                write!(f, "Letters are {} pixels high, expected 6 or 10", height)
+            }
+            OcrError::UnknownGlyph { index, glyph } => {
+                write!(f, "Unrecognized letter at position {}:\n{}", index, glyph)
+            }
+        }
+    }
+}
+
+impl Debug for OcrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        Display::fmt(self, f)
+    }
+}
+
+// Columns left of the image are blank, as a cell may start before the text
+fn draw(rows: &[&[bool]], columns: Range<i64>) -> Vec<String> {
+    let lit =
+        |row: &[bool], x: i64| usize::try_from(x).ok().and_then(|x| row.get(x)) == Some(&true);
+
+    rows.iter()
+        .map(|row| {
+            columns
+                .clone()
+                .map(|x| if lit(row, x) { '#' } else { '.' })
+                .collect()
+        })
+        .collect()
+}
+
+// Letters are drawn in fixed cells, most with blank columns to spare
+fn trim<S: AsRef<str>>(pattern: &[S]) -> Vec<String> {
+    let pattern = pattern.iter().map(S::as_ref).collect::<Vec<_>>();
+    let ink = |x: &usize| pattern.iter().any(|row| row.as_bytes()[*x] == b'#');
+    let width = pattern[0].len();
+
+    let start = (0..width).find(ink).unwrap_or(0);
+    let end = (0..width).rev().find(ink).map_or(start, |x| x + 1);
+
+    pattern
+        .iter()
+        .map(|row| row[start..end].to_string())
+        .collect()
+}
+
+/// Reads the block letters Advent of Code draws, from rows of pixels that
+/// are `true` where lit. Letters sit in fixed cells, 5 pixels wide for the
+/// small font and 8 for the large one. As a letter may leave the start of its
+/// cell blank, each way of lining the cells up with the text is tried in turn,
+/// so any margin around the text is ignored.
+pub fn recognize<R: AsRef<[bool]>>(pixels: &[R]) -> Result<String, OcrError> {
+    let blank = |row: &&R| !row.as_ref().contains(&true);
+
+    let first = pixels
+        .iter()
+        .position(|row| !blank(&row))
+        .ok_or(OcrError::Empty)?;
+    let last = pixels.iter().rposition(|row| !blank(&row)).unwrap();
+    let rows = pixels[first..=last]
+        .iter()
+        .map(|row| row.as_ref())
+        .collect::<Vec<_>>();
+
+    let (font, cell) = match rows.len() {
+        6 => (SMALL, 5),
+        10 => (LARGE, 8),
+        height => return Err(OcrError::UnsupportedHeight(height)),
+    };
+
+    let ink = |x: usize| rows.iter().any(|row| row.get(x) == Some(&true));
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let left = (0..width).find(|&x| ink(x)).unwrap() as i64;
+    let right = (0..width).rev().find(|&x| ink(x)).unwrap() as i64 + 1;
+
+    let read = |offset: i64| {
+        (left - offset..right)
+            .step_by(cell)
+            .enumerate()
+            .map(|(index, x)| {
+                let glyph = trim(&draw(&rows, x..x + cell as i64));
+
+                font.iter()
+                    .find(|(_, pattern)| trim(pattern) == glyph)
+                    .map(|&(letter, _)| letter)
+                    .ok_or_else(|| OcrError::UnknownGlyph {
+                        index,
+                        glyph: glyph.join("\n"),
+                    })
+            })
+            .collect::<Result<String, _>>()
+    };
+
+    // If no alignment works, report the cells lined up with the first lit column
+    read(0).or_else(|error| {
+        (1..cell as i64)
+            .find_map(|offset| read(offset).ok())
+            .ok_or(error)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pixels(text: &str) -> Vec<Vec<bool>> {
+        text.lines()
+            .map(|line| line.chars().map(|c| c == '#').collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_small() {
+        let image = pixels(
+            "
+ ###   ##  #  # #### #  #  ##    ## #  #
+ #  # #  # #  # #    #  # #  #    # #  #
+ #  # #    #  # ###  #### #       # ####
+ ###  # ## #  # #    #  # #       # #  #
+ #    #  # #  # #    #  # #  # #  # #  #
+ #     ###  ##  #### #  #  ##   ##  #  #
+",
+        );
+
+        assert_eq!(Ok("PGUEHCJH".to_string()), recognize(&image));
+    }
+
+    #[test]
+    fn test_large() {
+        let image = pixels(
+            "
+#....#..######
+#....#..#.....
+.#..#...#.....
+.#..#...#.....
+..##....#####.
+..##....#.....
+.#..#...#.....
+.#..#...#.....
+#....#..#.....
+#....#..######
+",
+        );
+
+        assert_eq!(Ok("XE".to_string()), recognize(&image));
+    }
+
+    #[test]
+    fn test_full_width() {
+        // Y fills its cell, leaving no blank column before the next letter
+        let image = pixels(
+            "
+#...##..#
+#...##..#
+.#.#.####
+..#..#..#
+..#..#..#
+..#..#..#
+",
+        );
+
+        assert_eq!(Ok("YH".to_string()), recognize(&image));
+    }
+
+    #[test]
+    fn test_blank_start() {
+        // I leaves the first column of its cell blank
+        let image = pixels(
+            "
+.###.#..#
+..#..#..#
+..#..####
+..#..#..#
+..#..#..#
+.###.#..#
+",
+        );
+
+        assert_eq!(Ok("IH".to_string()), recognize(&image));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Err(OcrError::Empty), recognize(&pixels("....\n....")));
+        assert_eq!(
+            Err(OcrError::UnsupportedHeight(2)),
+            recognize(&pixels("#\n#"))
+        );
+        assert_eq!(
+            Err(OcrError::UnknownGlyph {
+                index: 1,
+                glyph: "##\n##\n##\n##\n##\n##".to_string()
+            }),
+            recognize(&pixels(
+                "###..##\n#..#.##\n#..#.##\n###..##\n#....##\n#....##"
+            ))
+        );
+    }
+}
@@ -0,0 +1,562 @@
+use crate::animation::{changes, Cell, Frame};
+use crate::export::Rgb;
+use crossterm::style::Color;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Somewhere to keep the frames of an animation. Frames come with the time
+/// they are shown, counted in frames rather than read from the clock, so the
+/// same run always records the same file.
+pub trait Recorder {
+    fn frame(&mut self, frame: &Frame, at: Duration);
+
+    /// Called once with the time the animation ended.
+    fn finish(&mut self, end: Duration) -> io::Result<()>;
+}
+
+/// Records an asciicast v2 file, which `asciinema play` can replay in a
+/// terminal.
+pub struct Asciicast {
+    path: PathBuf,
+    front: Option<Frame>,
+    width: usize,
+    height: usize,
+    events: Vec<(Duration, String)>,
+}
+
+impl Asciicast {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Asciicast {
+            path: path.as_ref().to_path_buf(),
+            front: None,
+            width: 0,
+            height: 0,
+            // Clear the screen and hide the cursor before the first frame
+            events: vec![(Duration::from_secs(0), "\x1b[2J\x1b[H\x1b[?25l".to_string())],
+        }
+    }
+
+    fn to_cast(&self, end: Duration) -> String {
+        let mut cast = format!(
+            "{{\"version\": 2, \"width\": {}, \"height\": {}}}\n",
+            self.width.max(1),
+            self.height + 1
+        );
+
+        let done = (end, format!("\x1b[{}H\x1b[?25h", self.height + 1));
+
+        for (at, output) in self.events.iter().chain(Some(&done)) {
+            writeln!(
+                cast,
+                "[{:.6}, \"o\", \"{}\"]",
+                at.as_secs_f64(),
+                json_escape(output)
+            )
+            .unwrap();
+        }
+
+        cast
+    }
+}
+
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::new();
+
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+impl Recorder for Asciicast {
+    fn frame(&mut self, frame: &Frame, at: Duration) {
+        let mut output = String::new();
+
+        for run in changes(self.front.as_ref(), frame) {
+            write!(output, "\x1b[{};{}H", run.y + 1, run.x + 1).unwrap();
+
+            for cell in run.cells {
+                write!(output, "{}", cell.styled()).unwrap();
+            }
+        }
+
+        self.width = self.width.max(frame.width());
+        self.height = self.height.max(frame.height());
+        self.front = Some(frame.clone());
+
+        if !output.is_empty() {
+            self.events.push((at, output));
+        }
+    }
+
+    fn finish(&mut self, end: Duration) -> io::Result<()> {
+        fs::write(&self.path, self.to_cast(end))
+    }
+}
+
+fn ansi(index: u8) -> Rgb {
+    const BASIC: [Rgb; 16] = [
+        [0, 0, 0],
+        [205, 0, 0],
+        [0, 205, 0],
+        [205, 205, 0],
+        [0, 0, 238],
+        [205, 0, 205],
+        [0, 205, 205],
+        [229, 229, 229],
+        [127, 127, 127],
+        [255, 0, 0],
+        [0, 255, 0],
+        [255, 255, 0],
+        [92, 92, 255],
+        [255, 0, 255],
+        [0, 255, 255],
+        [255, 255, 255],
+    ];
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+    match index {
+        0..=15 => BASIC[index as usize],
+        16..=231 => {
+            let cube = index - 16;
+            [
+                LEVELS[(cube / 36) as usize],
+                LEVELS[(cube / 6 % 6) as usize],
+                LEVELS[(cube % 6) as usize],
+            ]
+        }
+        _ => {
+            let grey = 8 + 10 * (index - 232);
+            [grey, grey, grey]
+        }
+    }
+}
+
+fn rgb(color: Color) -> Option<Rgb> {
+    let index = match color {
+        Color::Reset => return None,
+        Color::Rgb { r, g, b } => return Some([r, g, b]),
+        Color::AnsiValue(index) => index,
+        Color::Black => 0,
+        Color::DarkRed => 1,
+        Color::DarkGreen => 2,
+        Color::DarkYellow => 3,
+        Color::DarkBlue => 4,
+        Color::DarkMagenta => 5,
+        Color::DarkCyan => 6,
+        Color::Grey => 7,
+        Color::DarkGrey => 8,
+        Color::Red => 9,
+        Color::Green => 10,
+        Color::Yellow => 11,
+        Color::Blue => 12,
+        Color::Magenta => 13,
+        Color::Cyan => 14,
+        Color::White => 15,
+    };
+
+    Some(ansi(index))
+}
+
+const BACKGROUND: Rgb = [0, 0, 0];
+const FOREGROUND: Rgb = [229, 229, 229];
+
+/// A cell as a single color: its background, unless it has a character
+/// heavy enough to be seen at a few pixels across.
+fn pixel(cell: &Cell) -> Rgb {
+    match cell.c {
+        ' ' | '.' | '·' => cell.bg.and_then(rgb).unwrap_or(BACKGROUND),
+        _ => cell.fg.and_then(rgb).unwrap_or(FOREGROUND),
+    }
+}
+
+/// Records an animated GIF, drawing every cell as a square of one color.
+/// Frames closer together than viewers will show them are dropped.
+pub struct Gif {
+    path: PathBuf,
+    scale: usize,
+    palette: Vec<Rgb>,
+    colors: HashMap<Rgb, u8>,
+    /// When each frame is shown, with the palette index of each cell.
+    frames: Vec<(Duration, Vec<Vec<u8>>)>,
+    too_many_colors: bool,
+}
+
+// Most viewers slow down anything faster than 50 frames a second
+const MIN_DELAY: Duration = Duration::from_millis(20);
+
+impl Gif {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Gif {
+            path: path.as_ref().to_path_buf(),
+            scale: 4,
+            palette: vec![BACKGROUND],
+            colors: vec![(BACKGROUND, 0)].into_iter().collect(),
+            frames: Vec::new(),
+            too_many_colors: false,
+        }
+    }
+
+    /// Pixels across each cell.
+    pub fn scale(mut self, scale: usize) -> Self {
+        self.scale = scale.max(1);
+        self
+    }
+
+    fn index(&mut self, color: Rgb) -> u8 {
+        if let Some(&index) = self.colors.get(&color) {
+            return index;
+        }
+
+        if self.palette.len() == 256 {
+            self.too_many_colors = true;
+            return 0;
+        }
+
+        let index = self.palette.len() as u8;
+        self.palette.push(color);
+        self.colors.insert(color, index);
+        index
+    }
+
+    fn to_gif(&self, end: Duration) -> Vec<u8> {
+        let width = self
+            .frames
+            .iter()
+            .flat_map(|(_, cells)| cells.iter().map(|row| row.len()))
+            .max()
+            .unwrap_or(0);
+        let height = self
+            .frames
+            .iter()
+            .map(|(_, cells)| cells.len())
+            .max()
+            .unwrap_or(0);
+        let bits = (1..=8)
+            .find(|&bits| 1 << bits >= self.palette.len())
+            .unwrap();
+
+        let mut out = b"GIF89a".to_vec();
+        out.extend(&((width * self.scale) as u16).to_le_bytes());
+        out.extend(&((height * self.scale) as u16).to_le_bytes());
+        // Global color table, 8 bit color resolution
+        out.extend(&[0xf0 | (bits - 1) as u8, 0, 0]);
+
+        for index in 0..1 << bits {
+            out.extend(&self.palette.get(index).cloned().unwrap_or(BACKGROUND));
+        }
+
+        // Loop forever
+        out.extend(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+        let mut shown = vec![vec![0; width]; height];
+        let times = self
+            .frames
+            .iter()
+            .map(|(at, _)| *at)
+            .skip(1)
+            .chain(Some(end));
+
+        for (frame, ((at, cells), next)) in self.frames.iter().zip(times).enumerate() {
+            let delay = ((next.max(*at) - *at).as_millis() as f64 / 10.0).round() as u16;
+
+            // After the first frame only the rectangle that changed needs
+            // drawing, the rest is kept
+            let changed = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .filter(|&(x, y)| frame == 0 || cell_at(cells, x, y) != shown[y][x])
+                .collect::<Vec<_>>();
+
+            let (left, top, right, bottom) = if changed.is_empty() {
+                (0, 0, 0, 0)
+            } else {
+                (
+                    changed.iter().map(|&(x, _)| x).min().unwrap(),
+                    changed.iter().map(|&(_, y)| y).min().unwrap(),
+                    changed.iter().map(|&(x, _)| x).max().unwrap(),
+                    changed.iter().map(|&(_, y)| y).max().unwrap(),
+                )
+            };
+
+            for &(x, y) in &changed {
+                shown[y][x] = cell_at(cells, x, y);
+            }
+
+            // Graphic control: leave the frame in place, then wait
+            out.extend(&[0x21, 0xf9, 0x04, 0x04]);
+            out.extend(&delay.to_le_bytes());
+            out.extend(&[0, 0]);
+
+            out.push(0x2c);
+            for value in &[left, top, right - left + 1, bottom - top + 1] {
+                out.extend(&((value * self.scale) as u16).to_le_bytes());
+            }
+            out.push(0);
+
+            let pixels = (top * self.scale..(bottom + 1) * self.scale)
+                .flat_map(|y| {
+                    let row = &shown[y / self.scale];
+                    (left * self.scale..(right + 1) * self.scale).map(move |x| row[x / self.scale])
+                })
+                .collect::<Vec<_>>();
+
+            let min_code_size = bits.max(2) as u8;
+            out.push(min_code_size);
+            for block in lzw(&pixels, min_code_size).chunks(255) {
+                out.push(block.len() as u8);
+                out.extend(block);
+            }
+            out.push(0);
+        }
+
+        out.push(0x3b);
+        out
+    }
+}
+
+fn cell_at(cells: &[Vec<u8>], x: usize, y: usize) -> u8 {
+    cells
+        .get(y)
+        .and_then(|row| row.get(x))
+        .cloned()
+        .unwrap_or(0)
+}
+
+impl Recorder for Gif {
+    fn frame(&mut self, frame: &Frame, at: Duration) {
+        let cells = frame
+            .rows()
+            .map(|row| row.iter().map(|cell| self.index(pixel(cell))).collect())
+            .collect();
+
+        match self.frames.last_mut() {
+            Some(last) if at < last.0 + MIN_DELAY => last.1 = cells,
+            _ => self.frames.push((at, cells)),
+        }
+    }
+
+    fn finish(&mut self, end: Duration) -> io::Result<()> {
+        if self.too_many_colors {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "GIF can only use 256 colors",
+            ));
+        }
+
+        fs::write(&self.path, self.to_gif(end))
+    }
+}
+
+/// Packs codes into bytes least significant bit first, as GIF wants.
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= u32::from(code) << self.bits;
+        self.bits += size;
+
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Compresses palette indices with GIF's variable width LZW.
+fn lzw(indices: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_code_size;
+    let end = clear + 1;
+
+    let mut out = BitWriter {
+        bytes: Vec::new(),
+        buffer: 0,
+        bits: 0,
+    };
+    let mut codes = HashMap::new();
+    let mut next = end + 1;
+    let mut size = u32::from(min_code_size) + 1;
+
+    out.write(clear, size);
+
+    let mut indices = indices.iter();
+    let mut prefix = match indices.next() {
+        Some(&index) => u16::from(index),
+        None => {
+            out.write(end, size);
+            return out.finish();
+        }
+    };
+
+    for &index in indices {
+        if let Some(&code) = codes.get(&(prefix, index)) {
+            prefix = code;
+            continue;
+        }
+
+        out.write(prefix, size);
+
+        if next == 4096 {
+            // The table is full, start again
+            out.write(clear, size);
+            codes.clear();
+            next = end + 1;
+            size = u32::from(min_code_size) + 1;
+        } else {
+            codes.insert((prefix, index), next);
+            next += 1;
+
+            // The decoder adds each code a step later, so widen a step later
+            if u32::from(next) > 1 << size && size < 12 {
+                size += 1;
+            }
+        }
+
+        prefix = u16::from(index);
+    }
+
+    out.write(prefix, size);
+    out.write(end, size);
+    out.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::animation::text_frame;
+
+    // The decoder from the GIF specification, for checking the encoder
+    fn unlzw(bytes: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear = 1usize << min_code_size;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut size = min_code_size as usize + 1;
+        let mut previous: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        let (mut buffer, mut bits, mut bytes) = (0usize, 0, bytes.iter());
+
+        loop {
+            while bits < size {
+                buffer |= (*bytes.next().unwrap() as usize) << bits;
+                bits += 8;
+            }
+            let code = buffer & ((1 << size) - 1);
+            buffer >>= size;
+            bits -= size;
+
+            if code == clear {
+                table = (0..clear + 2).map(|index| vec![index as u8]).collect();
+                size = min_code_size as usize + 1;
+                previous = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return out;
+            }
+
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => {
+                    let mut entry = previous.clone();
+                    entry.push(previous[0]);
+                    entry
+                }
+                (None, None) => panic!("code {} before any output", code),
+            };
+
+            if let Some(mut previous) = previous.take() {
+                previous.push(entry[0]);
+                table.push(previous);
+                if table.len() == 1 << size && size < 12 {
+                    size += 1;
+                }
+            }
+
+            out.extend(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw() {
+        let short = [1, 1, 1, 1, 0, 2, 2, 1, 1, 1];
+        assert_eq!(&short[..], &unlzw(&lzw(&short, 2)[..], 2)[..]);
+
+        // Long and varied enough to widen the codes to 12 bits and clear
+        let long = (0..40_000u32)
+            .map(|i| ((i * i / 7 + i / 3) % 5) as u8)
+            .collect::<Vec<_>>();
+        assert_eq!(long, unlzw(&lzw(&long, 3), 3));
+    }
+
+    #[test]
+    fn test_asciicast() {
+        let mut cast = Asciicast::new("unused.cast");
+        cast.frame(&text_frame("ab\ncd"), Duration::from_millis(0));
+        cast.frame(&text_frame("ab\n\"d"), Duration::from_millis(100));
+        cast.frame(&text_frame("ab\n\"d"), Duration::from_millis(200));
+
+        let text = cast.to_cast(Duration::from_millis(300));
+        let lines = text.lines().collect::<Vec<_>>();
+
+        assert_eq!(r#"{"version": 2, "width": 2, "height": 3}"#, lines[0]);
+        // Unchanged frames add nothing, changed ones only move to what changed
+        assert_eq!(5, lines.len());
+        assert_eq!(r#"[0.100000, "o", "\u001b[2;1H\""]"#, lines[3]);
+        assert!(lines[4].starts_with("[0.300000, "));
+    }
+
+    #[test]
+    fn test_gif() {
+        let mut frame = text_frame("#.\n..");
+        let mut gif = Gif::new("unused.gif").scale(2);
+
+        gif.frame(&frame, Duration::from_millis(0));
+        // Too soon after the last one to be seen, so it replaces it
+        *frame.get_mut((1, 1)).unwrap() = Cell::new('o').fg(Color::Red);
+        gif.frame(&frame, Duration::from_millis(10));
+        *frame.get_mut((0, 0)).unwrap() = Cell::new('.');
+        gif.frame(&frame, Duration::from_millis(500));
+
+        let bytes = gif.to_gif(Duration::from_millis(1000));
+
+        assert_eq!(b"GIF89a", &bytes[..6]);
+        assert_eq!(&[4, 0, 4, 0], &bytes[6..10]);
+        assert_eq!(vec![BACKGROUND, FOREGROUND, ansi(9)], gif.palette);
+        assert_eq!(2, gif.frames.len());
+        assert_eq!(Some(&0x3b), bytes.last());
+
+        // The second frame only redraws the top left cell, after half a second
+        let control = bytes
+            .windows(4)
+            .rposition(|window| window == [0x21, 0xf9, 0x04, 0x04])
+            .unwrap();
+        assert_eq!(&[50, 0], &bytes[control + 4..control + 6]);
+        assert_eq!(
+            &[0x2c, 0, 0, 0, 0, 2, 0, 2, 0, 0, 2],
+            &bytes[control + 8..control + 19]
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use crate::grid::Grid;
+use std::fmt::{Debug, Display, Error, Formatter};
+
+#[derive(Eq, PartialEq)]
+pub enum SifError {
+    EmptySize,
+    Empty,
+    /// The last layer stopped after `found` of its `expected` pixels.
+    Truncated {
+        layer: usize,
+        found: usize,
+        expected: usize,
+    },
+    StrayNewline(usize),
+    /// The pixel at `index` in the encoded data is not a digit.
+    InvalidPixel {
+        index: usize,
+        found: char,
+    },
+    /// The pixel at `index` across the given layers is over 9.
+    PixelOutOfRange {
+        index: usize,
+        value: u8,
+    },
+    LayerSize {
+        layer: usize,
+        width: usize,
+        height: usize,
+    },
+}
+
+impl Display for SifError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        match self {
+            SifError::EmptySize => write!(f, "Images must be at least one pixel wide and high"),
+            SifError::Empty => write!(f, "Image has no layers"),
+            SifError::Truncated {
+                layer,
+                found,
+                expected,
+            } => write!(
+                f,
+                "Layer {} has {} pixels, expected {}",
+                layer, found, expected
+            ),
+            SifError::StrayNewline(index) => write!(f, "Unexpected newline at {}", index),
+            SifError::InvalidPixel { index, found } => {
+                write!(f, "Expected a digit at {}, found {:?}", index, found)
+            }
+            SifError::PixelOutOfRange { index, value } => {
+                write!(f, "Expected a digit at {}, found {}", index, value)
+            }
+            SifError::LayerSize {
+                layer,
+                width,
+                height,
+            } => write!(
+                f,
+                "Layer {} is {}x{}, unlike the layers before it",
+                layer, width, height
+            ),
+        }
+    }
+}
+
+impl Debug for SifError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        Display::fmt(self, f)
+    }
+}
+
+/// An image in the Space Image Format: layers of digits, stacked with the
+/// first layer in front.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SifImage {
+    width: usize,
+    height: usize,
+    layers: Vec<Grid<u8>>,
+}
+
+impl SifImage {
+    /// Reads layers of `width` by `height` digits. A single newline may end
+    /// the data, as it does when read from a file.
+    pub fn decode(data: &[u8], width: usize, height: usize) -> Result<Self, SifError> {
+        if width == 0 || height == 0 {
+            return Err(SifError::EmptySize);
+        }
+
+        let data = match data {
+            [rest @ .., b'\r', b'\n'] | [rest @ .., b'\n'] => rest,
+            _ => data,
+        };
+
+        let pixels = data
+            .iter()
+            .enumerate()
+            .map(|(index, &byte)| match byte {
+                b'0'..=b'9' => Ok(byte - b'0'),
+                b'\r' | b'\n' => Err(SifError::StrayNewline(index)),
+                _ => Err(SifError::InvalidPixel {
+                    index,
+                    found: byte as char,
+                }),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let size = width * height;
+
+        if pixels.is_empty() {
+            return Err(SifError::Empty);
+        }
+
+        if pixels.len() % size != 0 {
+            return Err(SifError::Truncated {
+                layer: pixels.len() / size,
+                found: pixels.len() % size,
+                expected: size,
+            });
+        }
+
+        let layers = pixels
+            .chunks_exact(size)
+            .map(|layer| Grid::from_rows(layer.chunks_exact(width).map(<[u8]>::to_vec).collect()))
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        Ok(SifImage {
+            width,
+            height,
+            layers,
+        })
+    }
+
+    /// Stacks layers into an image, front layer first. Pixels must be digits
+    /// from 0 to 9, so the image can be encoded.
+    pub fn from_layers(layers: Vec<Grid<u8>>) -> Result<Self, SifError> {
+        let (width, height) = match layers.first() {
+            Some(layer) => (layer.width(), layer.height()),
+            None => return Err(SifError::Empty),
+        };
+
+        if width == 0 || height == 0 {
+            return Err(SifError::EmptySize);
+        }
+
+        if let Some((layer, grid)) = layers
+            .iter()
+            .enumerate()
+            .find(|(_, grid)| (grid.width(), grid.height()) != (width, height))
+        {
+            return Err(SifError::LayerSize {
+                layer,
+                width: grid.width(),
+                height: grid.height(),
+            });
+        }
+
+        let pixels = layers.iter().flat_map(|grid| grid.rows().flatten());
+        if let Some((index, &pixel)) = pixels.enumerate().find(|&(_, &pixel)| pixel > 9) {
+            return Err(SifError::PixelOutOfRange {
+                index,
+                value: pixel,
+            });
+        }
+
+        Ok(SifImage {
+            width,
+            height,
+            layers,
+        })
+    }
+
+    pub fn encode(&self) -> String {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.iter())
+            .map(|(_, &pixel)| (b'0' + pixel) as char)
+            .collect()
+    }
+
+    pub fn layer(&self, index: usize) -> Option<&Grid<u8>> {
+        self.layers.get(index)
+    }
+
+    pub fn layers(&self) -> impl Iterator<Item = &Grid<u8>> {
+        self.layers.iter()
+    }
+
+    /// How many of each digit a layer has.
+    pub fn histogram(&self, index: usize) -> Option<[usize; 10]> {
+        let layer = self.layer(index)?;
+        let mut counts = [0; 10];
+
+        for (_, &pixel) in layer.iter() {
+            counts[pixel as usize] += 1;
+        }
+
+        Some(counts)
+    }
+
+    /// Each pixel is the frontmost one that isn't `transparent`, or
+    /// `transparent` if they all are.
+    pub fn composite(&self, transparent: u8) -> Grid<u8> {
+        let mut image = Grid::new(self.width, self.height, transparent);
+
+        for layer in self.layers.iter().rev() {
+            for (pos, &pixel) in layer.iter() {
+                if pixel != transparent {
+                    *image.get_mut(pos).unwrap() = pixel;
+                }
+            }
+        }
+
+        image
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let image = SifImage::decode(b"123456789012\n", 3, 2).unwrap();
+
+        assert_eq!(2, image.layers().count());
+        assert_eq!(Some(&[7, 8, 9][..]), image.layer(1).unwrap().row(0));
+        assert_eq!(Some([1, 1, 1, 0, 0, 0, 0, 1, 1, 1]), image.histogram(1));
+        assert_eq!(None, image.histogram(2));
+        assert_eq!("123456789012", image.encode());
+    }
+
+    #[test]
+    fn test_composite() {
+        let image = SifImage::decode(b"0222112222120000", 2, 2).unwrap();
+        assert_eq!(Some(&[0, 1][..]), image.composite(2).row(0));
+        assert_eq!(Some(&[1, 0][..]), image.composite(2).row(1));
+
+        // With 0 as the see-through value the back layers show instead
+        assert_eq!(Some(&[1, 2][..]), image.composite(0).row(0));
+        assert_eq!(Some(&[2, 2][..]), image.composite(0).row(1));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Err(SifError::Truncated {
+                layer: 1,
+                found: 2,
+                expected: 6
+            }),
+            SifImage::decode(b"12345678", 3, 2)
+        );
+        assert_eq!(
+            Err(SifError::StrayNewline(3)),
+            SifImage::decode(b"123\n456", 3, 2)
+        );
+        assert_eq!(
+            Err(SifError::InvalidPixel {
+                index: 1,
+                found: 'x'
+            }),
+            SifImage::decode(b"1x3456", 3, 2)
+        );
+        assert_eq!(Err(SifError::Empty), SifImage::decode(b"\n", 3, 2));
+        assert_eq!(Err(SifError::EmptySize), SifImage::decode(b"1", 0, 2));
+    }
+
+    #[test]
+    fn test_from_layers() {
+        let front = Grid::from_rows(vec![vec![2, 1], vec![0, 2]]).unwrap();
+        let back = Grid::from_rows(vec![vec![1, 1], vec![1, 0]]).unwrap();
+        let image = SifImage::from_layers(vec![front.clone(), back]).unwrap();
+
+        assert_eq!("21021110", image.encode());
+        assert_eq!(Ok(image.clone()), SifImage::decode(b"21021110", 2, 2));
+
+        let wide = Grid::new(3, 2, 0);
+        assert_eq!(
+            Err(SifError::LayerSize {
+                layer: 1,
+                width: 3,
+                height: 2
+            }),
+            SifImage::from_layers(vec![front.clone(), wide])
+        );
+
+        let bright = Grid::from_rows(vec![vec![1, 1], vec![10, 1]]).unwrap();
+        assert_eq!(
+            Err(SifError::PixelOutOfRange {
+                index: 6,
+                value: 10
+            }),
+            SifImage::from_layers(vec![front, bright])
+        );
+    }
+}